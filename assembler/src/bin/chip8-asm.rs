@@ -0,0 +1,52 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+struct Options {
+    source_path: PathBuf,
+    output_path: Option<PathBuf>,
+}
+
+fn parse_arguments() -> Options {
+    let mut args = env::args();
+    let program_name = args.next().unwrap_or_else(|| "chip8-asm".to_string());
+    let usage = || -> ! {
+        eprintln!("Usage: {} [-o <output.ch8>] <source.asm>", program_name);
+        std::process::exit(1);
+    };
+
+    let mut source_path = None;
+    let mut output_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output_path = Some(args.next().map(PathBuf::from).unwrap_or_else(|| usage())),
+            _ if arg.starts_with('-') => usage(),
+            _ => source_path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    Options {
+        source_path: source_path.unwrap_or_else(|| usage()),
+        output_path,
+    }
+}
+
+fn main() {
+    let options = parse_arguments();
+    let program = match assembler::assemble_file(&options.source_path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let output_path = options
+        .output_path
+        .unwrap_or_else(|| options.source_path.with_extension("ch8"));
+    if let Err(err) = fs::write(&output_path, &program.bytes) {
+        eprintln!("Error: Failed to write {}: {}", output_path.display(), err);
+        std::process::exit(1);
+    }
+    println!("Assembled {} bytes to {}", program.bytes.len(), output_path.display());
+}
@@ -0,0 +1,89 @@
+use crate::lexer::{Token, TokenKind};
+use crate::AsmError;
+
+/// Binary operators from the loosest to the tightest binding
+const PRECEDENCE: [&[&str]; 6] = [&["|"], &["^"], &["&"], &["<<", ">>"], &["+", "-"], &["*", "/", "%"]];
+
+/// Evaluates a constant expression over numbers, symbols and `$` (the current address).
+/// `lookup` resolves a symbol name found at the given column.
+/// `column` is reported if the expression is missing entirely.
+pub(crate) fn evaluate(
+    tokens: &[Token],
+    column: usize,
+    lookup: &dyn Fn(&str, usize) -> Result<i64, AsmError>,
+) -> Result<i64, AsmError> {
+    if tokens.is_empty() {
+        return Err(AsmError::at(column, "expected an expression"));
+    }
+    let mut parser = Parser { tokens, position: 0, lookup };
+    let value = parser.binary(0)?;
+    match tokens.get(parser.position) {
+        None => Ok(value),
+        Some(token) => Err(AsmError::at(token.column, "unexpected token in expression")),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    lookup: &'a dyn Fn(&str, usize) -> Result<i64, AsmError>,
+}
+
+impl Parser<'_> {
+    fn end_column(&self) -> usize {
+        self.tokens.last().map_or(0, |token| token.column + 1)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i64, AsmError> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut value = self.binary(level + 1)?;
+        while let Some(token) = self.tokens.get(self.position) {
+            let TokenKind::Symbol(op) = token.kind else { break };
+            if !PRECEDENCE[level].contains(&op) {
+                break;
+            }
+            self.position += 1;
+            let rhs = self.binary(level + 1)?;
+            value = match op {
+                "|" => value | rhs,
+                "^" => value ^ rhs,
+                "&" => value & rhs,
+                "<<" => value.checked_shl(rhs as u32).unwrap_or(0),
+                ">>" => value.checked_shr(rhs as u32).unwrap_or(0),
+                "+" => value.wrapping_add(rhs),
+                "-" => value.wrapping_sub(rhs),
+                "*" => value.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err(AsmError::at(token.column, "division by zero")),
+                "/" => value.wrapping_div(rhs),
+                _ => value.wrapping_rem(rhs),
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i64, AsmError> {
+        let Some(token) = self.tokens.get(self.position) else {
+            return Err(AsmError::at(self.end_column(), "expected a value"));
+        };
+        self.position += 1;
+        match &token.kind {
+            TokenKind::Number(value) => Ok(*value),
+            TokenKind::Ident(name) => (self.lookup)(name, token.column),
+            TokenKind::Symbol("-") => Ok(self.unary()?.wrapping_neg()),
+            TokenKind::Symbol("~") => Ok(!self.unary()?),
+            TokenKind::Symbol("(") => {
+                let value = self.binary(0)?;
+                match self.tokens.get(self.position) {
+                    Some(close) if close.is_symbol(")") => {
+                        self.position += 1;
+                        Ok(value)
+                    }
+                    _ => Err(AsmError::at(token.column, "unclosed '('")),
+                }
+            }
+            _ => Err(AsmError::at(token.column, "expected a value")),
+        }
+    }
+}
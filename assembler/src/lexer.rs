@@ -0,0 +1,110 @@
+use crate::AsmError;
+
+/// One token of a source line
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TokenKind {
+    Ident(String),
+    Number(i64),
+    Str(String),
+    Symbol(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Token {
+    pub kind: TokenKind,
+    /// 1-based column of the first character
+    pub column: usize,
+}
+
+impl Token {
+    pub fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.kind, TokenKind::Symbol(s) if s == symbol)
+    }
+
+    pub fn ident(&self) -> Option<&str> {
+        match &self.kind {
+            TokenKind::Ident(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+const SYMBOLS: [&str; 17] = ["<<", ">>", ",", ":", "(", ")", "[", "]", "+", "-", "*", "/", "%", "&", "|", "^", "~"];
+
+/// Splits one line into tokens, stopping at a `;` comment.
+/// Errors carry only a column; the caller fills in the file and line.
+pub(crate) fn tokenize(line: &str) -> Result<Vec<Token>, AsmError> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c == ';' {
+            break;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().filter(|&&c| c != '_').collect();
+            let value = parse_integer(&text).ok_or_else(|| AsmError::at(column, format!("invalid number '{}'", text)))?;
+            tokens.push(Token { kind: TokenKind::Number(value), column });
+        } else if c.is_alphabetic() || c == '_' || c == '.' || c == '$' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token { kind: TokenKind::Ident(chars[start..i].iter().collect()), column });
+        } else if c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(AsmError::at(column, "unterminated string")),
+                    Some('"') => break,
+                    Some('\\') => {
+                        let escaped = match chars.get(i + 1) {
+                            Some('n') => '\n',
+                            Some('0') => '\0',
+                            Some(&other @ ('\\' | '"')) => other,
+                            _ => return Err(AsmError::at(i + 1, "invalid escape sequence")),
+                        };
+                        text.push(escaped);
+                        i += 2;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push(Token { kind: TokenKind::Str(text), column });
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .ok_or_else(|| AsmError::at(column, format!("unexpected character '{}'", c)))?;
+            i += symbol.len();
+            tokens.push(Token { kind: TokenKind::Symbol(symbol), column });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses a decimal, `0x` hexadecimal or `0b` binary integer
+fn parse_integer(text: &str) -> Option<i64> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
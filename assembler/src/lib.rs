@@ -0,0 +1,424 @@
+//! Two-pass assembler for CHIP-8, SUPER-CHIP and XO-CHIP programs.
+//!
+//! Source files hold one statement per line, with `;` starting a comment:
+//!
+//! ```text
+//! define SPEED 2          ; constants, also written `SPEED equ 2`
+//!     org 0x200           ; set the address of the next byte
+//! start:                  ; labels end with a colon
+//!     LD I, ball
+//!     ADD V0, SPEED
+//!     JP start
+//! ball:
+//!     sprite "..##..", ".####.", "..##.."
+//!     db 0x3C, "text", -1 ; bytes and strings
+//!     dw 0x1234, start    ; big-endian words
+//!     include "more.asm"  ; relative to the including file
+//! ```
+//!
+//! Mnemonics use the syntax printed by `chip8_core::Instruction`, so the
+//! output of `chip8-disasm` assembles back into the original ROM. Operands
+//! may be expressions over numbers, symbols and `$` (the current address)
+//! with `+ - * / % & | ^ << >> ~` and parentheses.
+
+mod expr;
+mod lexer;
+
+use chip8_core::{Instruction, Operand, ParseError, STARTING_ADDRESS};
+use lexer::{Token, TokenKind};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name used in errors for source that did not come from a file
+const INPUT_NAME: &str = "<input>";
+/// Highest address a program can reach
+const MAX_ADDRESS: u32 = 0xFFFF;
+
+/// An assembly error, located at a file, line and column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    /// 1-based line, or 0 if the error is about the file as a whole
+    pub line: usize,
+    /// 1-based column
+    pub column: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    /// Creates an error at a column of a line that is not known yet
+    pub(crate) fn at(column: usize, message: impl Into<String>) -> Self {
+        Self {
+            file: String::new(),
+            line: 0,
+            column,
+            message: message.into(),
+        }
+    }
+
+    /// Fills in the file and line, unless an included file already did
+    fn located(mut self, file: &str, line: usize) -> Self {
+        if self.line == 0 {
+            self.file = file.to_string();
+            self.line = line;
+        }
+        self
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// An assembled program, ready for `Chip8::load_program`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    /// Bytes from `STARTING_ADDRESS` up to the last one assembled
+    pub bytes: Vec<u8>,
+    /// The address of every label
+    pub symbols: BTreeMap<String, u16>,
+}
+
+/// Assembles source text; includes are resolved against the current directory
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    Assembler::default().run(INPUT_NAME, Path::new("."), source)
+}
+
+/// Assembles a source file; includes are resolved against its directory
+pub fn assemble_file(path: impl AsRef<Path>) -> Result<Program, AsmError> {
+    let path = path.as_ref();
+    let source = read_source(path)?;
+    let mut assembler = Assembler::default();
+    assembler.includes.push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+    assembler.run(&path.display().to_string(), path.parent().unwrap_or(Path::new(".")), &source)
+}
+
+fn read_source(path: &Path) -> Result<String, AsmError> {
+    fs::read_to_string(path).map_err(|err| AsmError {
+        file: path.display().to_string(),
+        line: 0,
+        column: 0,
+        message: format!("cannot read file: {}", err),
+    })
+}
+
+#[derive(Default)]
+struct Assembler {
+    symbols: HashMap<String, i64>,
+    labels: BTreeMap<String, u16>,
+    /// Symbols defined so far in the current pass
+    defined: HashSet<String>,
+    address: u32,
+    final_pass: bool,
+    image: Vec<u8>,
+    written: Vec<bool>,
+    /// Files currently being assembled, to catch recursive includes
+    includes: Vec<PathBuf>,
+}
+
+impl Assembler {
+    fn run(mut self, name: &str, dir: &Path, source: &str) -> Result<Program, AsmError> {
+        // The first pass only finds the addresses of labels, so forward references
+        // evaluate to 0 there and are resolved in the second
+        for final_pass in [false, true] {
+            self.final_pass = final_pass;
+            self.address = STARTING_ADDRESS as u32;
+            self.defined.clear();
+            self.image.clear();
+            self.written.clear();
+            self.source(name, dir, source)?;
+        }
+
+        let end = self.written.iter().rposition(|&written| written).map_or(0, |last| last + 1);
+        self.image.truncate(end);
+        Ok(Program {
+            bytes: self.image,
+            symbols: self.labels,
+        })
+    }
+
+    fn source(&mut self, name: &str, dir: &Path, source: &str) -> Result<(), AsmError> {
+        for (index, line) in source.lines().enumerate() {
+            self.statement(line, dir).map_err(|err| err.located(name, index + 1))?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, line: &str, dir: &Path) -> Result<(), AsmError> {
+        let tokens = lexer::tokenize(line)?;
+        let mut rest = &tokens[..];
+
+        if let [label, colon, ..] = rest {
+            if let (Some(name), true) = (label.ident(), colon.is_symbol(":")) {
+                self.define_label(name, label.column)?;
+                rest = &rest[2..];
+            }
+        }
+
+        let Some(head) = rest.first() else {
+            return Ok(());
+        };
+        let name = head
+            .ident()
+            .ok_or_else(|| AsmError::at(head.column, "expected a label, directive or mnemonic"))?;
+        let args = &rest[1..];
+
+        if let Some(equ) = args.first().filter(|token| token.ident().is_some_and(|word| word.eq_ignore_ascii_case("equ"))) {
+            return self.define_constant(name, head.column, &args[1..], equ.column);
+        }
+
+        match name.to_ascii_lowercase().as_str() {
+            "org" => {
+                let address = self.evaluate(args, head.column + 3, true)?;
+                if !(0..=MAX_ADDRESS as i64).contains(&address) {
+                    return Err(AsmError::at(args[0].column, format!("address {:#X} is out of range", address)));
+                }
+                self.address = address as u32;
+            }
+            "define" => match args {
+                [constant, value @ ..] if constant.ident().is_some() => {
+                    let end = constant.column + constant.ident().unwrap().len();
+                    self.define_constant(constant.ident().unwrap(), constant.column, value, end)?;
+                }
+                _ => return Err(AsmError::at(head.column + 6, "expected a constant name after 'define'")),
+            },
+            "db" => {
+                for (group, column) in split_operands(args, head.column + 2)? {
+                    if let [Token { kind: TokenKind::Str(text), .. }] = group {
+                        self.emit(text.as_bytes(), column)?;
+                    } else {
+                        let value = self.evaluate(group, column, false)?;
+                        let byte = fit(value, -0x80, 0xFF, column)? as u8;
+                        self.emit(&[byte], column)?;
+                    }
+                }
+            }
+            "dw" => {
+                for (group, column) in split_operands(args, head.column + 2)? {
+                    let value = self.evaluate(group, column, false)?;
+                    let word = fit(value, -0x8000, 0xFFFF, column)? as u16;
+                    self.emit(&word.to_be_bytes(), column)?;
+                }
+            }
+            "sprite" => {
+                for (group, column) in split_operands(args, head.column + 6)? {
+                    let [Token { kind: TokenKind::Str(row), .. }] = group else {
+                        return Err(AsmError::at(column, "expected a quoted sprite row such as \"..##..\""));
+                    };
+                    let bytes = sprite_row(row, column)?;
+                    self.emit(&bytes, column)?;
+                }
+            }
+            "include" => {
+                let [Token { kind: TokenKind::Str(file), column }] = args else {
+                    return Err(AsmError::at(head.column + 7, "expected a quoted file name after 'include'"));
+                };
+                self.include(&dir.join(file), *column)?;
+            }
+            _ => self.instruction(name, head.column, args)?,
+        }
+        Ok(())
+    }
+
+    fn define_label(&mut self, name: &str, column: usize) -> Result<(), AsmError> {
+        if self.address > MAX_ADDRESS {
+            return Err(AsmError::at(column, format!("label '{}' is past the end of memory", name)));
+        }
+        self.define(name, column, self.address as i64)?;
+        self.labels.insert(name.to_string(), self.address as u16);
+        Ok(())
+    }
+
+    fn define_constant(&mut self, name: &str, column: usize, value: &[Token], end: usize) -> Result<(), AsmError> {
+        let value = self.evaluate(value, end + 1, false)?;
+        self.define(name, column, value)
+    }
+
+    fn define(&mut self, name: &str, column: usize, value: i64) -> Result<(), AsmError> {
+        if Operand::keyword(name).is_some() {
+            return Err(AsmError::at(column, format!("'{}' is a register or keyword", name)));
+        }
+        if !self.defined.insert(name.to_string()) {
+            return Err(AsmError::at(column, format!("'{}' is already defined", name)));
+        }
+        self.symbols.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Evaluates an expression. Outside the final pass, unknown symbols count as
+    /// 0 unless `now` asks for a value that affects the layout.
+    fn evaluate(&self, tokens: &[Token], column: usize, now: bool) -> Result<i64, AsmError> {
+        let strict = now || self.final_pass;
+        expr::evaluate(tokens, column, &|name, column| {
+            if name == "$" {
+                return Ok(self.address as i64);
+            }
+            match self.symbols.get(name) {
+                Some(&value) if !now || self.defined.contains(name) => Ok(value),
+                _ if strict => Err(AsmError::at(column, format!("undefined symbol '{}'", name))),
+                _ => Ok(0),
+            }
+        })
+    }
+
+    fn include(&mut self, path: &Path, column: usize) -> Result<(), AsmError> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.includes.contains(&canonical) {
+            return Err(AsmError::at(column, format!("'{}' includes itself", path.display())));
+        }
+        let source = read_source(path).map_err(|err| AsmError::at(column, err.message))?;
+        self.includes.push(canonical);
+        let result = self.source(&path.display().to_string(), path.parent().unwrap_or(Path::new(".")), &source);
+        self.includes.pop();
+        result
+    }
+
+    fn instruction(&mut self, mnemonic: &str, column: usize, args: &[Token]) -> Result<(), AsmError> {
+        let mut operands = Vec::new();
+        let mut long_address = None;
+        let groups = if args.is_empty() {
+            Vec::new()
+        } else {
+            split_operands(args, column + mnemonic.len())?
+        };
+        for &(group, column) in &groups {
+            let keyword = match group {
+                [token] => token.ident().and_then(Operand::keyword),
+                [open, index, close] if open.is_symbol("[") && close.is_symbol("]") => index
+                    .ident()
+                    .filter(|name| name.eq_ignore_ascii_case("I"))
+                    .map(|_| Operand::IndirectIndex),
+                _ => None,
+            };
+            let operand = match (keyword, group) {
+                (Some(keyword), _) => keyword,
+                (None, [long, address @ ..]) if long.ident().is_some_and(|word| word.eq_ignore_ascii_case("LONG")) => {
+                    let value = self.evaluate(address, long.column + 4, false)?;
+                    long_address = Some(fit(value, 0, 0xFFFF, address[0].column)? as u16);
+                    Operand::Long
+                }
+                _ => {
+                    let value = self.evaluate(group, column, false)?;
+                    let value = if (-0x80..0).contains(&value) { value & 0xFF } else { value };
+                    Operand::Value(fit(value, 0, 0xFFFF, column)? as u16)
+                }
+            };
+            operands.push(operand);
+        }
+
+        let operand_column = groups.first().map_or(column, |&(_, column)| column);
+        let instruction = Instruction::assemble(mnemonic, &operands).map_err(|err| match err {
+            ParseError::UnknownMnemonic(_) => AsmError::at(column, format!("unknown mnemonic '{}'", mnemonic)),
+            ParseError::ValueOutOfRange { value, .. } => {
+                // Point at the operand that holds the value
+                let position = operands.iter().position(|&operand| operand == Operand::Value(value));
+                let column = position.map_or(operand_column, |position| groups[position].1);
+                AsmError::at(column, err.to_string())
+            }
+            err => AsmError::at(operand_column, err.to_string()),
+        })?;
+        self.emit(&instruction.encode().to_be_bytes(), column)?;
+        if let Some(address) = long_address {
+            self.emit(&address.to_be_bytes(), operand_column)?;
+        }
+        Ok(())
+    }
+
+    /// Writes bytes at the current address and advances it
+    fn emit(&mut self, bytes: &[u8], column: usize) -> Result<(), AsmError> {
+        for &byte in bytes {
+            if self.address < STARTING_ADDRESS as u32 {
+                return Err(AsmError::at(
+                    column,
+                    format!("address {:#X} is below the program start {:#X}", self.address, STARTING_ADDRESS),
+                ));
+            }
+            if self.address > MAX_ADDRESS {
+                return Err(AsmError::at(column, "program runs past the end of memory"));
+            }
+
+            let offset = (self.address - STARTING_ADDRESS as u32) as usize;
+            if self.image.len() <= offset {
+                self.image.resize(offset + 1, 0);
+                self.written.resize(offset + 1, false);
+            }
+            if self.written[offset] {
+                return Err(AsmError::at(
+                    column,
+                    format!("address {:#X} has already been assembled", self.address),
+                ));
+            }
+            self.image[offset] = byte;
+            self.written[offset] = true;
+            self.address += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Splits operands at top-level commas, returning each with its column.
+/// `column` is where an operand was expected if the list is empty.
+fn split_operands(tokens: &[Token], column: usize) -> Result<Vec<(&[Token], usize)>, AsmError> {
+    let mut groups = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut expected_at = column;
+    for (index, token) in tokens.iter().enumerate() {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth -= 1;
+        } else if token.is_symbol(",") && depth == 0 {
+            if index == start {
+                return Err(AsmError::at(token.column, "expected an operand before ','"));
+            }
+            groups.push((&tokens[start..index], tokens[start].column));
+            start = index + 1;
+            expected_at = token.column + 1;
+        }
+    }
+    if start == tokens.len() {
+        return Err(AsmError::at(expected_at, "expected an operand"));
+    }
+    groups.push((&tokens[start..], tokens[start].column));
+    Ok(groups)
+}
+
+/// Checks that a value fits a field
+fn fit(value: i64, min: i64, max: i64, column: usize) -> Result<i64, AsmError> {
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(AsmError::at(column, format!("value {} does not fit in {}..={}", value, min, max)))
+    }
+}
+
+/// Turns a sprite row such as `"..####.."` into bytes, left-aligned and
+/// 8 pixels per byte. `#`, `X`, `*` and `1` are set; `.`, `_`, `0` and spaces are clear.
+fn sprite_row(row: &str, column: usize) -> Result<Vec<u8>, AsmError> {
+    if row.is_empty() || row.chars().count() > 16 {
+        return Err(AsmError::at(column, "sprite rows must be 1 to 16 pixels wide"));
+    }
+    let mut bits = Vec::new();
+    for pixel in row.chars() {
+        match pixel {
+            '#' | 'X' | 'x' | '*' | '1' => bits.push(true),
+            '.' | '_' | '0' | ' ' => bits.push(false),
+            other => return Err(AsmError::at(column, format!("invalid sprite pixel '{}'", other))),
+        }
+    }
+    Ok(bits
+        .chunks(8)
+        .map(|chunk| chunk.iter().enumerate().fold(0, |byte, (bit, &set)| byte | ((set as u8) << (7 - bit))))
+        .collect())
+}
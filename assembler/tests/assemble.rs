@@ -0,0 +1,105 @@
+use assembler::{assemble, assemble_file};
+use chip8_core::{disassemble, Platform, STARTING_ADDRESS};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn disassembled_roms_assemble_back_to_the_original() {
+    let games = Path::new(env!("CARGO_MANIFEST_DIR")).join("../c8games");
+    for entry in fs::read_dir(&games).unwrap() {
+        let path = entry.unwrap().path();
+        let rom = fs::read(&path).unwrap();
+        let listing = disassemble(&rom, STARTING_ADDRESS, Platform::XoChip).to_string();
+        let program = assemble(&listing).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        assert_eq!(program.bytes, rom, "{} did not round-trip", path.display());
+    }
+}
+
+#[test]
+fn labels_constants_and_data() {
+    let source = r##"
+define SPEED 2
+TOP equ SPEED * 3 + 1
+start:
+    LD I, sprite
+    ADD V0, SPEED
+    SE V1, TOP
+    JP start
+    LD I, LONG end
+sprite:
+    sprite "..####..", "#......#", "XXXXXXXXXXXXXXXX"
+    db "AB", -1, 0b1010
+    dw start, $
+end:
+"##;
+    let program = assemble(source).unwrap();
+    assert_eq!(
+        program.bytes,
+        [
+            0xA2, 0x0C, 0x70, 0x02, 0x31, 0x07, 0x12, 0x00, 0xF0, 0x00, 0x02, 0x18, // code
+            0x3C, 0x81, 0xFF, 0xFF, // sprite
+            0x41, 0x42, 0xFF, 0x0A, // db
+            0x02, 0x00, 0x02, 0x16, // dw
+        ]
+    );
+    assert_eq!(program.symbols["start"], 0x200);
+    assert_eq!(program.symbols["sprite"], 0x20C);
+    assert_eq!(program.symbols["end"], 0x218);
+}
+
+#[test]
+fn org_leaves_a_gap_of_zeros() {
+    let program = assemble("CLS\norg 0x206\nRET").unwrap();
+    assert_eq!(program.bytes, [0x00, 0xE0, 0, 0, 0, 0, 0x00, 0xEE]);
+}
+
+#[test]
+fn errors_report_line_and_column() {
+    let err = assemble("CLS\n    JP nowhere").unwrap_err();
+    assert_eq!((err.line, err.column), (2, 8));
+    assert_eq!(err.to_string(), "<input>:2:8: undefined symbol 'nowhere'");
+
+    let err = assemble("  FOO V0").unwrap_err();
+    assert_eq!((err.line, err.column, err.message.as_str()), (1, 3, "unknown mnemonic 'FOO'"));
+
+    let err = assemble("LD V0, 0x100").unwrap_err();
+    assert_eq!((err.line, err.column), (1, 8));
+
+    let err = assemble("a:\na: CLS").unwrap_err();
+    assert_eq!(err.to_string(), "<input>:2:1: 'a' is already defined");
+
+    let err = assemble("org 0x100\nCLS").unwrap_err();
+    assert_eq!(err.line, 2);
+}
+
+#[test]
+fn include_is_relative_to_the_including_file() {
+    let dir = std::env::temp_dir().join(format!("chip8-asm-test-{}", std::process::id()));
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(dir.join("main.asm"), "CALL draw\nJP $\ninclude \"lib/draw.asm\"\n").unwrap();
+    fs::write(dir.join("lib/draw.asm"), "draw:\n    include \"sprites.asm\"\n").unwrap();
+    fs::write(dir.join("lib/sprites.asm"), "    RET\n    BAD\n").unwrap();
+
+    let err = assemble_file(dir.join("main.asm")).unwrap_err();
+    assert!(err.file.ends_with("sprites.asm"), "{}", err);
+    assert_eq!(err.line, 2);
+
+    fs::write(dir.join("lib/sprites.asm"), "    RET\n").unwrap();
+    let program = assemble_file(dir.join("main.asm")).unwrap();
+    assert_eq!(program.bytes, [0x22, 0x04, 0x12, 0x02, 0x00, 0xEE]);
+
+    fs::write(dir.join("lib/sprites.asm"), "include \"draw.asm\"\n").unwrap();
+    let err = assemble_file(dir.join("main.asm")).unwrap_err();
+    assert!(err.message.contains("includes itself"), "{}", err);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn expression_arithmetic_wraps() {
+    let program = assemble("MIN equ -0x7FFFFFFFFFFFFFFF - 1\ndb MIN / -1 & 0xFF, MIN % -1, 7 / -2, 7 % -2").unwrap();
+    assert_eq!(program.bytes, [0x00, 0x00, 0xFD, 0x01]);
+
+    let err = assemble("db 1 % 0").unwrap_err();
+    assert_eq!((err.column, err.message.as_str()), (6, "division by zero"));
+}
@@ -0,0 +1,51 @@
+use crate::{Chip8, Platform};
+
+/// Number of bytes in the XO-CHIP audio pattern buffer
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+/// Pitch register value that plays the pattern at 4000 bits per second
+pub const DEFAULT_PITCH: u8 = 64;
+
+const PATTERN_BITS: f64 = (AUDIO_PATTERN_SIZE * 8) as f64;
+
+impl Chip8 {
+    /// Returns the current value of the sound timer
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Returns true while the sound timer is running and the buzzer should sound
+    pub fn is_buzzer_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Returns true once an XO-CHIP program has loaded an audible pattern with F002.
+    /// Until then the pattern is all zeros, and frontends should play their usual buzzer tone.
+    pub fn has_audio_pattern(&self) -> bool {
+        self.platform == Platform::XoChip && self.audio_pattern.iter().any(|&byte| byte != 0)
+    }
+
+    /// Returns the playback rate of the audio pattern in bits per second
+    pub fn audio_playback_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+
+    /// Renders the XO-CHIP audio pattern as PCM samples at the given sample rate.
+    ///
+    /// Samples are +1.0 or -1.0 for set and clear pattern bits while the sound
+    /// timer is running, and 0.0 while it is stopped. The playback position is
+    /// kept between calls, so consecutive buffers join up without clicks.
+    pub fn render_audio(&mut self, samples: &mut [f32], sample_rate: u32) {
+        if self.sound_timer == 0 {
+            samples.fill(0.0);
+            return;
+        }
+
+        let step = self.audio_playback_rate() / sample_rate as f64;
+        for sample in samples.iter_mut() {
+            let bit = self.audio_phase as usize;
+            let set = self.audio_pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            *sample = if set { 1.0 } else { -1.0 };
+            self.audio_phase = (self.audio_phase + step) % PATTERN_BITS;
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use chip8_core::{parse_number, Chip8, Debugger, Instruction, Platform, StopReason, WatchKind, DEFAULT_SEED};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+/// Cycles `continue` runs before giving control back, so a stuck program cannot hang the prompt
+const CONTINUE_CYCLE_LIMIT: u64 = 10_000_000;
+
+const HELP: &str = "\
+Commands:
+  break ADDR | break op PATTERN   stop at an address, or on opcodes such as DXYN or 8XY4
+  delete ADDR | delete op         remove a breakpoint or watchpoint, or every opcode breakpoint
+  watch [read|write] ADDR[-END]   stop when an instruction touches memory
+  step [N] (s)                    execute N instructions
+  next (n)                        step over a CALL
+  finish                          run until the current subroutine returns
+  continue (c)                    run until something stops execution
+  regs (r)                        show the registers
+  x/N ADDR                        dump N bytes of memory, e.g. x/16 I
+  disasm [ADDR] [N] (d)           disassemble N instructions
+  set REG=VALUE                   set V0-VF, I, PC, DT or ST
+  bt                              show the call stack as return addresses
+  screen                          draw the display in ASCII
+  key K up|down                   release or press key K (0-F)
+  info break                      list breakpoints
+  quit (q)
+An empty line repeats the last command. ADDR may be a number, I, PC or a symbol.";
+
+struct Options {
+    rom_path: String,
+    platform: Platform,
+    seed: u64,
+    symbols_path: Option<String>,
+}
+
+fn parse_arguments() -> Options {
+    let mut args = env::args();
+    let program_name = args.next().unwrap_or_else(|| "chip8-dbg".to_string());
+    let usage = || -> ! {
+        eprintln!(
+            "Usage: {} [--platform chip8|schip|xochip] [--seed N] [--symbols FILE] <path_to_rom>",
+            program_name
+        );
+        std::process::exit(1);
+    };
+
+    let mut rom_path = None;
+    let mut platform = Platform::Chip8;
+    let mut seed = DEFAULT_SEED;
+    let mut symbols_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => platform = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--seed" => seed = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--symbols" => symbols_path = Some(args.next().unwrap_or_else(|| usage())),
+            _ if arg.starts_with("--") => usage(),
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    Options {
+        rom_path: rom_path.unwrap_or_else(|| usage()),
+        platform,
+        seed,
+        symbols_path,
+    }
+}
+
+/// Reads a symbol table with one `ADDRESS NAME` pair per line, as written by `chip8-octo --symbols`
+fn load_symbols(path: &str) -> BTreeMap<String, u16> {
+    let text = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Error: Failed to read {}: {}", path, err);
+        std::process::exit(1);
+    });
+    text.lines()
+        .filter_map(|line| {
+            let (addr, name) = line.trim().split_once(char::is_whitespace)?;
+            Some((name.trim().to_string(), parse_number(addr)?))
+        })
+        .collect()
+}
+
+struct Session {
+    chip8: Chip8,
+    debugger: Debugger,
+    symbols: BTreeMap<String, u16>,
+    names: BTreeMap<u16, String>,
+}
+
+impl Session {
+    /// Resolves a number, register name or symbol to an address
+    fn address(&self, text: &str) -> Result<u16, String> {
+        match text.to_ascii_uppercase().as_str() {
+            "I" => return Ok(self.chip8.index_register()),
+            "PC" => return Ok(self.chip8.program_counter()),
+            _ => {}
+        }
+        self.symbols
+            .get(text)
+            .copied()
+            .or_else(|| parse_number(text))
+            .ok_or_else(|| format!("unknown address '{}'", text))
+    }
+
+    /// Formats an address with the symbol at or before it
+    fn describe(&self, addr: u16) -> String {
+        match self.names.range(..=addr).next_back() {
+            Some((&start, name)) if start == addr => format!("{:#05X} <{}>", addr, name),
+            Some((&start, name)) if addr - start < 0x100 => format!("{:#05X} <{}+{}>", addr, name, addr - start),
+            _ => format!("{:#05X}", addr),
+        }
+    }
+
+    fn instruction_line(&self, addr: u16) -> String {
+        let memory = self.chip8.memory();
+        let Some(bytes) = memory.get(addr as usize..addr as usize + 2) else {
+            return format!("{}: <out of memory>", self.describe(addr));
+        };
+        let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+        let text = match Instruction::decode(opcode) {
+            Ok(instruction) => instruction.to_string(),
+            Err(_) => format!("db {:#04X}, {:#04X}", bytes[0], bytes[1]),
+        };
+        let marker = if addr == self.chip8.program_counter() { "=>" } else { "  " };
+        format!("{} {}: {:04X}  {}", marker, self.describe(addr), opcode, text)
+    }
+
+    fn report(&self, reason: StopReason) {
+        if reason != StopReason::Stepped {
+            println!("Stopped: {}", reason);
+        }
+        println!("{}", self.instruction_line(self.chip8.program_counter()));
+    }
+
+    /// Runs one command, returning false when the session should end
+    fn execute(&mut self, line: &str) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+        let args: Vec<&str> = words.collect();
+
+        if let Some(count) = command.strip_prefix("x/") {
+            let count = count.parse::<u16>().map_err(|_| format!("invalid count '{}'", count))?;
+            let addr = self.address(args.first().copied().unwrap_or("I"))?;
+            self.dump(addr, count);
+            return Ok(true);
+        }
+
+        match command {
+            "break" | "b" => match args.as_slice() {
+                ["op", pattern] => {
+                    let (mask, value) = opcode_pattern(pattern)?;
+                    self.debugger.add_opcode_breakpoint(mask, value);
+                    println!("Breaking on opcodes matching {}", pattern.to_ascii_uppercase());
+                }
+                [addr] => {
+                    let addr = self.address(addr)?;
+                    self.debugger.add_breakpoint(addr);
+                    println!("Breakpoint at {}", self.describe(addr));
+                }
+                _ => return Err("usage: break ADDR | break op PATTERN".to_string()),
+            },
+            "delete" if args == ["op"] => self.debugger.clear_opcode_breakpoints(),
+            "delete" => {
+                let addr = self.address(args.first().ok_or("usage: delete ADDR | delete op")?)?;
+                let removed = self.debugger.remove_breakpoint(addr) | self.debugger.remove_watchpoint(addr);
+                if !removed {
+                    return Err(format!("nothing set at {:#05X}", addr));
+                }
+            }
+            "watch" => {
+                let (kind, range) = match args.as_slice() {
+                    ["read", range] => (WatchKind::Read, range),
+                    ["write", range] => (WatchKind::Write, range),
+                    [range] => (WatchKind::Access, range),
+                    _ => return Err("usage: watch [read|write] ADDR[-END]".to_string()),
+                };
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (self.address(start)?, self.address(end)?),
+                    None => (self.address(range)?, self.address(range)?),
+                };
+                self.debugger.add_watchpoint(start..=end, kind);
+                println!("Watching {:#05X}-{:#05X}", start, end);
+            }
+            "info" if args == ["break"] => {
+                for addr in self.debugger.breakpoints() {
+                    println!("  {}", self.describe(addr));
+                }
+            }
+            "step" | "s" => {
+                let count = match args.first() {
+                    Some(count) => count.parse().map_err(|_| format!("invalid count '{}'", count))?,
+                    None => 1,
+                };
+                let mut reason = StopReason::Stepped;
+                for _ in 0..count {
+                    reason = self.debugger.step(&mut self.chip8);
+                    if reason != StopReason::Stepped {
+                        break;
+                    }
+                }
+                self.report(reason);
+            }
+            "next" | "n" => {
+                let reason = self.debugger.step_over(&mut self.chip8, CONTINUE_CYCLE_LIMIT);
+                self.report(reason);
+            }
+            "finish" => {
+                let reason = self.debugger.step_out(&mut self.chip8, CONTINUE_CYCLE_LIMIT);
+                self.report(reason);
+            }
+            "continue" | "c" => {
+                let reason = self.debugger.resume(&mut self.chip8, CONTINUE_CYCLE_LIMIT);
+                self.report(reason);
+            }
+            "regs" | "r" => self.print_registers(),
+            "disasm" | "d" => {
+                let addr = match args.first() {
+                    Some(addr) => self.address(addr)?,
+                    None => self.chip8.program_counter(),
+                };
+                let count: usize = match args.get(1) {
+                    Some(count) => count.parse().map_err(|_| format!("invalid count '{}'", count))?,
+                    None => 10,
+                };
+                // Past one pass over memory the listing would only repeat itself
+                for index in 0..count.min(self.chip8.memory().len() / 2) {
+                    let addr = addr.wrapping_add((index * 2) as u16);
+                    if let Some(name) = self.names.get(&addr) {
+                        println!("{}:", name);
+                    }
+                    println!("{}", self.instruction_line(addr));
+                }
+            }
+            "set" => self.set(&args.join(""))?,
+            "bt" => {
+                println!("#0  {}", self.describe(self.chip8.program_counter()));
+                for (depth, &return_address) in self.chip8.call_stack().iter().rev().enumerate() {
+                    println!("#{}  return to {}", depth + 1, self.describe(return_address));
+                }
+            }
+            "screen" => self.print_screen(),
+            "key" => {
+                let (key, pressed) = match args.as_slice() {
+                    [key, "down"] => (key, true),
+                    [key, "up"] => (key, false),
+                    _ => return Err("usage: key K up|down".to_string()),
+                };
+                let key = u8::from_str_radix(key, 16)
+                    .ok()
+                    .filter(|&key| key < 16)
+                    .ok_or_else(|| format!("invalid key '{}'", key))?;
+                self.chip8.set_key_state(key as usize, pressed);
+            }
+            "help" | "h" => println!("{}", HELP),
+            "quit" | "q" => return Ok(false),
+            _ => return Err(format!("unknown command '{}', try 'help'", command)),
+        }
+        Ok(true)
+    }
+
+    fn print_registers(&self) {
+        for first in [0, 8] {
+            let registers: Vec<String> = (first..first + 8)
+                .map(|x| format!("V{:X}={:02X}", x, self.chip8.register(x)))
+                .collect();
+            println!("{}", registers.join(" "));
+        }
+        println!(
+            "I={:04X} PC={:04X} SP={:X} DT={:02X} ST={:02X}",
+            self.chip8.index_register(),
+            self.chip8.program_counter(),
+            self.chip8.stack_pointer(),
+            self.chip8.delay_timer(),
+            self.chip8.sound_timer()
+        );
+    }
+
+    fn dump(&self, addr: u16, count: u16) {
+        let memory = self.chip8.memory();
+        let start = addr as usize;
+        let end = (start + count as usize).min(memory.len());
+        for (row, chunk) in memory[start.min(end)..end].chunks(16).enumerate() {
+            let bytes: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+            println!("{:04X}: {}", start + row * 16, bytes.join(" "));
+        }
+    }
+
+    fn set(&mut self, assignment: &str) -> Result<(), String> {
+        let (target, value) = assignment.split_once('=').ok_or("usage: set REG=VALUE")?;
+        let value = parse_number(value).ok_or_else(|| format!("invalid value '{}'", value))?;
+        let target = target.to_ascii_uppercase();
+        let byte = || u8::try_from(value).map_err(|_| format!("{} does not fit in a byte", value));
+        match target.as_str() {
+            "I" => self.chip8.set_index_register(value),
+            "PC" => self.chip8.set_program_counter(value),
+            "DT" => self.chip8.set_delay_timer(byte()?),
+            "ST" => self.chip8.set_sound_timer(byte()?),
+            _ => {
+                let x = target
+                    .strip_prefix('V')
+                    .filter(|digit| digit.len() == 1)
+                    .and_then(|digit| usize::from_str_radix(digit, 16).ok())
+                    .ok_or_else(|| format!("unknown register '{}'", target))?;
+                self.chip8.set_register(x, byte()?);
+            }
+        }
+        Ok(())
+    }
+
+    fn print_screen(&self) {
+        let framebuffer = self.chip8.get_framebuffer();
+        let border = format!("+{}+", "-".repeat(framebuffer.width));
+        println!("{}", border);
+        for row in framebuffer.pixels.chunks(framebuffer.width) {
+            let line: String = row
+                .iter()
+                .map(|&pixel| match pixel {
+                    0 => ' ',
+                    1 => '#',
+                    2 => '+',
+                    _ => '@',
+                })
+                .collect();
+            println!("|{}|", line);
+        }
+        println!("{}", border);
+    }
+}
+
+/// Turns a pattern such as `DXYN` or `8XY4` into an opcode mask and value; X, Y and N match anything
+fn opcode_pattern(pattern: &str) -> Result<(u16, u16), String> {
+    if pattern.len() != 4 {
+        return Err(format!("opcode patterns have four digits, such as DXYN, not '{}'", pattern));
+    }
+    let mut mask = 0;
+    let mut value = 0;
+    for c in pattern.chars() {
+        mask <<= 4;
+        value <<= 4;
+        match c.to_ascii_uppercase() {
+            'X' | 'Y' | 'N' => {}
+            c => {
+                let digit = c.to_digit(16).ok_or_else(|| format!("invalid opcode pattern '{}'", pattern))?;
+                mask |= 0xF;
+                value |= digit as u16;
+            }
+        }
+    }
+    Ok((mask, value))
+}
+
+fn main() {
+    let options = parse_arguments();
+    let rom = fs::read(&options.rom_path).unwrap_or_else(|err| {
+        eprintln!("Error: Failed to read {}: {}", options.rom_path, err);
+        std::process::exit(1);
+    });
+
+    let mut chip8 = Chip8::new(options.platform, options.platform.default_quirks());
+    chip8.seed_rng(options.seed);
+    if let Err(err) = chip8.load_program(&rom) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+
+    let symbols = options.symbols_path.as_deref().map(load_symbols).unwrap_or_default();
+    let names = symbols.iter().map(|(name, &addr)| (addr, name.clone())).collect();
+    let mut session = Session {
+        chip8,
+        debugger: Debugger::new(),
+        symbols,
+        names,
+    };
+
+    println!("Loaded {} ({} bytes). Type 'help' for commands.", options.rom_path, rom.len());
+    println!("{}", session.instruction_line(session.chip8.program_counter()));
+
+    let stdin = io::stdin();
+    let mut last_command = String::new();
+    loop {
+        print!("(chip8) ");
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let line = line.trim();
+        let command = if line.is_empty() { last_command.clone() } else { line.to_string() };
+        match session.execute(&command) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(message) => println!("Error: {}", message),
+        }
+        last_command = command;
+    }
+}
@@ -0,0 +1,52 @@
+use chip8_core::{disassemble, parse_number, Platform, STARTING_ADDRESS};
+use std::env;
+use std::fs;
+
+struct Options {
+    rom_path: String,
+    platform: Platform,
+    origin: u16,
+}
+
+fn parse_arguments() -> Options {
+    let mut args = env::args();
+    let program_name = args.next().unwrap_or_else(|| "chip8-disasm".to_string());
+    let usage = || -> ! {
+        eprintln!("Usage: {} [--platform chip8|schip|xochip] [--origin ADDRESS] <path_to_rom>", program_name);
+        std::process::exit(1);
+    };
+
+    let mut rom_path = None;
+    let mut platform = Platform::Chip8;
+    let mut origin = STARTING_ADDRESS;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => platform = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--origin" => {
+                origin = args.next().as_deref().and_then(parse_number).unwrap_or_else(|| usage());
+            }
+            _ if arg.starts_with("--") => usage(),
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    Options {
+        rom_path: rom_path.unwrap_or_else(|| usage()),
+        platform,
+        origin,
+    }
+}
+
+fn main() {
+    let options = parse_arguments();
+    let rom = match fs::read(&options.rom_path) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("Error: Failed to read {}: {}", options.rom_path, err);
+            std::process::exit(1);
+        }
+    };
+
+    println!("; {}", options.rom_path);
+    print!("{}", disassemble(&rom, options.origin, options.platform));
+}
@@ -0,0 +1,85 @@
+use chip8_core::{serve_gdb, Chip8, Debugger, Platform, DEFAULT_SEED};
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+
+const DEFAULT_PORT: u16 = 1234;
+
+struct Options {
+    rom_path: String,
+    platform: Platform,
+    seed: u64,
+    port: u16,
+}
+
+fn parse_arguments() -> Options {
+    let mut args = env::args();
+    let program_name = args.next().unwrap_or_else(|| "chip8-gdbserver".to_string());
+    let usage = || -> ! {
+        eprintln!(
+            "Usage: {} [--platform chip8|schip|xochip] [--seed N] [--port PORT] <path_to_rom>",
+            program_name
+        );
+        std::process::exit(1);
+    };
+
+    let mut rom_path = None;
+    let mut platform = Platform::Chip8;
+    let mut seed = DEFAULT_SEED;
+    let mut port = DEFAULT_PORT;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => platform = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--seed" => seed = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--port" => port = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            _ if arg.starts_with("--") => usage(),
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    Options {
+        rom_path: rom_path.unwrap_or_else(|| usage()),
+        platform,
+        seed,
+        port,
+    }
+}
+
+fn main() {
+    let options = parse_arguments();
+    let rom = fs::read(&options.rom_path).unwrap_or_else(|err| {
+        eprintln!("Error: Failed to read {}: {}", options.rom_path, err);
+        std::process::exit(1);
+    });
+
+    let mut chip8 = Chip8::new(options.platform, options.platform.default_quirks());
+    chip8.seed_rng(options.seed);
+    if let Err(err) = chip8.load_program(&rom) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", options.port)).unwrap_or_else(|err| {
+        eprintln!("Error: Failed to listen on port {}: {}", options.port, err);
+        std::process::exit(1);
+    });
+    println!("Listening on 127.0.0.1:{} for a debugger", options.port);
+
+    let stream = match listener.accept() {
+        Ok((stream, peer)) => {
+            println!("Debugger connected from {}", peer);
+            stream
+        }
+        Err(err) => {
+            eprintln!("Error: Failed to accept a connection: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut debugger = Debugger::new();
+    if let Err(err) = serve_gdb(stream, &mut chip8, &mut debugger) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+    println!("Debugger disconnected");
+}
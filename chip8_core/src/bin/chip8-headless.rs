@@ -0,0 +1,233 @@
+use chip8_core::{encode_ascii, encode_pbm, encode_png, Chip8, Platform, Tracer, DEFAULT_SEED};
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+
+const DEFAULT_FRAMES: u64 = 60;
+const DEFAULT_CYCLES_PER_FRAME: u32 = 10;
+/// Largest --scale, which keeps a PNG of the 128x64 screen to about 32 MiB
+const MAX_SCALE: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Png,
+    Pbm,
+    Ascii,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Pbm => "pbm",
+            Format::Ascii => "txt",
+        }
+    }
+}
+
+/// Holds a key down from the start of frame `first` to the end of frame `last`
+struct KeyPress {
+    key: usize,
+    first: u64,
+    last: u64,
+}
+
+struct Options {
+    rom_path: String,
+    platform: Platform,
+    seed: u64,
+    frames: u64,
+    cycles_per_frame: u32,
+    presses: Vec<KeyPress>,
+    dumps: Vec<u64>,
+    format: Format,
+    scale: usize,
+    output: String,
+    trace_path: Option<String>,
+}
+
+fn parse_arguments() -> Options {
+    let mut args = env::args();
+    let program_name = args.next().unwrap_or_else(|| "chip8-headless".to_string());
+    let usage = || -> ! {
+        eprintln!(
+            "Usage: {} [--platform chip8|schip|xochip] [--seed N] [--frames N] [--cycles-per-frame N] \
+             [--press KEY@FRAME[-FRAME]]... [--dump FRAME[,FRAME...]] [--format png|pbm|ascii] [--scale N] \
+             [--output PREFIX] [--trace FILE] <path_to_rom>",
+            program_name
+        );
+        std::process::exit(1);
+    };
+
+    let mut rom_path = None;
+    let mut platform = Platform::Chip8;
+    let mut seed = DEFAULT_SEED;
+    let mut frames = DEFAULT_FRAMES;
+    let mut cycles_per_frame = DEFAULT_CYCLES_PER_FRAME;
+    let mut presses = Vec::new();
+    let mut dumps = Vec::new();
+    let mut format = Format::Png;
+    let mut scale = 1;
+    let mut output = "frame".to_string();
+    let mut trace_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => platform = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--seed" => seed = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--frames" => frames = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--cycles-per-frame" => {
+                cycles_per_frame = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&cycles| cycles > 0)
+                    .unwrap_or_else(|| usage());
+            }
+            "--press" => presses.push(args.next().as_deref().and_then(parse_press).unwrap_or_else(|| usage())),
+            "--dump" => {
+                let list = args.next().unwrap_or_else(|| usage());
+                for frame in list.split(',') {
+                    dumps.push(frame.trim().parse().unwrap_or_else(|_| usage()));
+                }
+            }
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("png") => Format::Png,
+                    Some("pbm") => Format::Pbm,
+                    Some("ascii") => Format::Ascii,
+                    _ => usage(),
+                }
+            }
+            "--scale" => {
+                scale = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|scale| (1..=MAX_SCALE).contains(scale))
+                    .unwrap_or_else(|| usage());
+            }
+            "--output" => output = args.next().unwrap_or_else(|| usage()),
+            "--trace" => trace_path = Some(args.next().unwrap_or_else(|| usage())),
+            _ if arg.starts_with("--") => usage(),
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    // Without any --dump the final screen is saved
+    if dumps.is_empty() {
+        dumps.push(frames);
+    }
+    if dumps.iter().any(|&frame| frame > frames) {
+        eprintln!("Error: Cannot dump a frame after the last of {} frames", frames);
+        std::process::exit(1);
+    }
+
+    Options {
+        rom_path: rom_path.unwrap_or_else(|| usage()),
+        platform,
+        seed,
+        frames,
+        cycles_per_frame,
+        presses,
+        dumps,
+        format,
+        scale,
+        output,
+        trace_path,
+    }
+}
+
+/// Parses `KEY@FRAME` or `KEY@FIRST-LAST`, where KEY is a hex digit
+fn parse_press(text: &str) -> Option<KeyPress> {
+    let (key, frames) = text.split_once('@')?;
+    let key = usize::from_str_radix(key, 16).ok().filter(|&key| key < 16)?;
+    let (first, last) = match frames.split_once('-') {
+        Some((first, last)) => (first.parse().ok()?, last.parse().ok()?),
+        None => {
+            let frame = frames.parse().ok()?;
+            (frame, frame)
+        }
+    };
+    (first <= last).then_some(KeyPress { key, first, last })
+}
+
+fn dump(chip8: &Chip8, frame: u64, options: &Options) {
+    let framebuffer = chip8.get_framebuffer();
+    let image = match options.format {
+        Format::Png => encode_png(&framebuffer, options.scale),
+        Format::Pbm => encode_pbm(&framebuffer, options.scale),
+        Format::Ascii => encode_ascii(&framebuffer),
+    };
+    let path = format!("{}_{:05}.{}", options.output, frame, options.format.extension());
+    if let Err(err) = fs::write(&path, image) {
+        eprintln!("Error: Failed to write {}: {}", path, err);
+        std::process::exit(1);
+    }
+    println!("Wrote {}", path);
+}
+
+fn main() {
+    let options = parse_arguments();
+    let rom = fs::read(&options.rom_path).unwrap_or_else(|err| {
+        eprintln!("Error: Failed to read {}: {}", options.rom_path, err);
+        std::process::exit(1);
+    });
+
+    let mut chip8 = Chip8::new(options.platform, options.platform.default_quirks());
+    chip8.seed_rng(options.seed);
+    if let Err(err) = chip8.load_program(&rom) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+
+    let mut tracer = options.trace_path.as_deref().map(|path| match File::create(path) {
+        Ok(file) => Tracer::new(BufWriter::new(file)),
+        Err(err) => {
+            eprintln!("Error: Failed to create {}: {}", path, err);
+            std::process::exit(1);
+        }
+    });
+
+    for frame in 0..=options.frames {
+        if options.dumps.contains(&frame) {
+            dump(&chip8, frame, &options);
+        }
+        if frame == options.frames {
+            break;
+        }
+
+        for key in 0..16 {
+            let pressed = options
+                .presses
+                .iter()
+                .any(|press| press.key == key && (press.first..=press.last).contains(&frame));
+            chip8.set_key_state(key, pressed);
+        }
+
+        // A program that exits leaves its last screen in place for the remaining dumps
+        for _ in 0..options.cycles_per_frame {
+            if chip8.is_halted() {
+                break;
+            }
+            if let Some(tracer) = tracer.as_mut() {
+                if let Err(err) = tracer.trace(&chip8) {
+                    eprintln!("Error: Failed to write trace: {}", err);
+                    std::process::exit(1);
+                }
+            }
+            if let Err(err) = chip8.cycle() {
+                eprintln!("Error: {} in frame {}", err, frame);
+                std::process::exit(1);
+            }
+        }
+        chip8.update_timers();
+        if let Some(tracer) = tracer.as_mut() {
+            tracer.end_frame();
+        }
+    }
+
+    if let Some(tracer) = tracer {
+        if let Err(err) = tracer.into_inner() {
+            eprintln!("Error: Failed to write trace: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
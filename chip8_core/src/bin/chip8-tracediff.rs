@@ -0,0 +1,141 @@
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Lines of shared history printed before the divergence
+const DEFAULT_CONTEXT: usize = 3;
+
+struct Options {
+    paths: [String; 2],
+    context: usize,
+}
+
+fn parse_arguments() -> Options {
+    let mut args = env::args();
+    let program_name = args.next().unwrap_or_else(|| "chip8-tracediff".to_string());
+    let usage = || -> ! {
+        eprintln!("Usage: {} [--context N] <trace_a> <trace_b>", program_name);
+        std::process::exit(2);
+    };
+
+    let mut paths = Vec::new();
+    let mut context = DEFAULT_CONTEXT;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--context" => context = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            _ if arg.starts_with("--") => usage(),
+            _ => paths.push(arg),
+        }
+    }
+
+    match <[String; 2]>::try_from(paths) {
+        Ok(paths) => Options { paths, context },
+        Err(_) => usage(),
+    }
+}
+
+/// Names the first field that differs between two trace lines, with both values
+fn first_difference(a: &str, b: &str) -> Option<(String, String, String)> {
+    let sections_a: Vec<&str> = a.split(" | ").collect();
+    let sections_b: Vec<&str> = b.split(" | ").collect();
+    if sections_a.len() != 3 || sections_b.len() != 3 {
+        return None;
+    }
+
+    let head = |line: &str| -> Vec<String> {
+        let mut fields = line.split_whitespace();
+        let mut head: Vec<String> = fields.by_ref().take(3).map(str::to_string).collect();
+        head.push(fields.collect::<Vec<_>>().join(" "));
+        head
+    };
+    let head_names = ["cycle", "PC", "opcode", "instruction"].map(str::to_string);
+    let register_names = (0..16).map(|x| format!("V{:X}", x));
+    let names = head_names.into_iter().chain(register_names);
+    let fields = |sections: &[&str]| -> Vec<String> {
+        let mut fields = head(sections[0]);
+        fields.extend(sections[1].split_whitespace().map(str::to_string));
+        fields
+    };
+
+    let fields_a = fields(&sections_a);
+    let fields_b = fields(&sections_b);
+    if let Some((name, (value_a, value_b))) = names
+        .zip(fields_a.iter().zip(&fields_b))
+        .find(|(_, (value_a, value_b))| value_a != value_b)
+    {
+        return Some((name, value_a.clone(), value_b.clone()));
+    }
+
+    sections_a[2]
+        .split_whitespace()
+        .zip(sections_b[2].split_whitespace())
+        .find(|(field_a, field_b)| field_a != field_b)
+        .map(|(field_a, field_b)| {
+            let (name, value_a) = field_a.split_once('=').unwrap_or(("field", field_a));
+            let value_b = field_b.split_once('=').map_or(field_b, |(_, value)| value);
+            (name.to_string(), value_a.to_string(), value_b.to_string())
+        })
+}
+
+fn open(path: &str) -> impl Iterator<Item = String> {
+    let file = File::open(path).unwrap_or_else(|err| {
+        eprintln!("Error: Failed to open {}: {}", path, err);
+        std::process::exit(2);
+    });
+    let path = path.to_string();
+    BufReader::new(file).lines().map(move |line| {
+        line.unwrap_or_else(|err| {
+            eprintln!("Error: Failed to read {}: {}", path, err);
+            std::process::exit(2);
+        })
+    })
+}
+
+fn main() {
+    let options = parse_arguments();
+    let [path_a, path_b] = &options.paths;
+    let mut lines_a = open(path_a);
+    let mut lines_b = open(path_b);
+    let mut history = Vec::new();
+
+    for line_number in 1.. {
+        let (line_a, line_b) = match (lines_a.next(), lines_b.next()) {
+            (None, None) => {
+                println!("Traces match ({} lines)", line_number - 1);
+                return;
+            }
+            (Some(_), None) => {
+                println!("{} ends at line {}, {} continues", path_b, line_number - 1, path_a);
+                std::process::exit(1);
+            }
+            (None, Some(_)) => {
+                println!("{} ends at line {}, {} continues", path_a, line_number - 1, path_b);
+                std::process::exit(1);
+            }
+            (Some(line_a), Some(line_b)) => (line_a, line_b),
+        };
+
+        if line_a.trim_end() == line_b.trim_end() {
+            history.push(line_a);
+            if history.len() > options.context {
+                history.remove(0);
+            }
+            continue;
+        }
+
+        let cycle = line_a.split_whitespace().next().unwrap_or("?");
+        match first_difference(&line_a, &line_b) {
+            Some((field, value_a, value_b)) => println!(
+                "Traces diverge at line {} (cycle {}): {} is {} vs {}",
+                line_number, cycle, field, value_a, value_b
+            ),
+            None => println!("Traces diverge at line {} (cycle {})", line_number, cycle),
+        }
+        for line in &history {
+            println!("  {}", line);
+        }
+        println!("< {}", line_a);
+        println!("> {}", line_b);
+        std::process::exit(1);
+    }
+}
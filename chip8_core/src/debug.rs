@@ -0,0 +1,308 @@
+use crate::{Chip8, Chip8Error, Instruction, AUDIO_PATTERN_SIZE};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// Cycles run between timer updates unless configured otherwise
+pub const DEFAULT_CYCLES_PER_FRAME: u32 = 10;
+
+/// The kind of memory access a watchpoint reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+impl WatchKind {
+    fn matches(self, access: Access) -> bool {
+        match self {
+            WatchKind::Read => access == Access::Read,
+            WatchKind::Write => access == Access::Write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+/// A single memory access made by an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Why the debugger handed control back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// The requested step, step-over or step-out finished
+    Stepped,
+    /// The next instruction is at a breakpoint
+    Breakpoint { pc: u16 },
+    /// The instruction at `pc` touched watched memory and has executed
+    Watchpoint { pc: u16, addr: u16, access: Access },
+    /// The next instruction matches an opcode breakpoint
+    Opcode { pc: u16, instruction: Instruction },
+    /// The program exited through 00FD
+    Halted,
+    /// The machine raised an error
+    Error(Chip8Error),
+    /// The cycle budget ran out first
+    CycleLimit,
+    /// A step-out was asked for outside of any subroutine
+    NotInSubroutine,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Stepped => write!(f, "stepped"),
+            StopReason::Breakpoint { pc } => write!(f, "breakpoint at {:#05X}", pc),
+            StopReason::Watchpoint { pc, addr, access } => {
+                let verb = match access {
+                    Access::Read => "read",
+                    Access::Write => "write",
+                };
+                write!(f, "watchpoint: {} of {:#05X} at {:#05X}", verb, addr, pc)
+            }
+            StopReason::Opcode { pc, instruction } => write!(f, "opcode breakpoint: {} at {:#05X}", instruction, pc),
+            StopReason::Halted => write!(f, "program exited"),
+            StopReason::Error(err) => write!(f, "error: {}", err),
+            StopReason::CycleLimit => write!(f, "cycle limit reached"),
+            StopReason::NotInSubroutine => write!(f, "not in a subroutine"),
+        }
+    }
+}
+
+/// Breaks on every opcode where `opcode & mask == value`, e.g. mask 0xF000 and value 0xD000 for DXYN
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeBreakpoint {
+    pub mask: u16,
+    pub value: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Watchpoint {
+    range: RangeInclusive<u16>,
+    kind: WatchKind,
+}
+
+/// Runs a `Chip8` under control of breakpoints, watchpoints and stepping.
+///
+/// Breakpoints are checked before an instruction executes, so the machine is
+/// left at the instruction that triggered them. The first instruction of every
+/// run is exempt, which lets execution continue past the stop it last reported.
+/// Watchpoints stop just after the instruction that made the access, so the
+/// memory already holds the value it wrote.
+/// The timers are updated every `cycles_per_frame` cycles, as a frontend would.
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    opcode_breakpoints: Vec<OpcodeBreakpoint>,
+    cycles_per_frame: u32,
+    cycles: u64,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    /// Creates a debugger with no breakpoints
+    pub fn new() -> Self {
+        Self {
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            opcode_breakpoints: Vec::new(),
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            cycles: 0,
+        }
+    }
+
+    /// Sets how many cycles run between timer updates
+    pub fn set_cycles_per_frame(&mut self, cycles: u32) {
+        self.cycles_per_frame = cycles.max(1);
+    }
+
+    /// Returns the number of cycles run so far
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Adds a breakpoint, returning false if there already was one
+    pub fn add_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.insert(addr)
+    }
+
+    /// Removes a breakpoint, returning false if there was none
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    /// Returns the breakpoint addresses in order
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Watches a range of memory for reads, writes or both
+    pub fn add_watchpoint(&mut self, range: RangeInclusive<u16>, kind: WatchKind) {
+        self.watchpoints.push(Watchpoint { range, kind });
+    }
+
+    /// Removes every watchpoint that overlaps `addr`, returning false if there were none
+    pub fn remove_watchpoint(&mut self, addr: u16) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| !watchpoint.range.contains(&addr));
+        self.watchpoints.len() != before
+    }
+
+    /// Removes the watchpoint set with exactly this range and kind, returning false if there was none
+    pub fn remove_watchpoint_range(&mut self, range: RangeInclusive<u16>, kind: WatchKind) -> bool {
+        let watchpoint = Watchpoint { range, kind };
+        match self.watchpoints.iter().position(|existing| *existing == watchpoint) {
+            Some(index) => {
+                self.watchpoints.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Breaks on every opcode where `opcode & mask == value`
+    pub fn add_opcode_breakpoint(&mut self, mask: u16, value: u16) {
+        self.opcode_breakpoints.push(OpcodeBreakpoint { mask, value: value & mask });
+    }
+
+    /// Removes every opcode breakpoint
+    pub fn clear_opcode_breakpoints(&mut self) {
+        self.opcode_breakpoints.clear();
+    }
+
+    /// Executes one instruction
+    pub fn step(&mut self, chip8: &mut Chip8) -> StopReason {
+        self.run(chip8, 1, |_| true)
+    }
+
+    /// Runs until something stops execution or `max_cycles` have run
+    pub fn resume(&mut self, chip8: &mut Chip8, max_cycles: u64) -> StopReason {
+        self.run(chip8, max_cycles, |_| false)
+    }
+
+    /// Executes one instruction, running a 2NNN call through to its return
+    pub fn step_over(&mut self, chip8: &mut Chip8, max_cycles: u64) -> StopReason {
+        let is_call = matches!(next_instruction(chip8), Some(Instruction::Call { .. }));
+        if !is_call {
+            return self.step(chip8);
+        }
+        let return_address = chip8.program_counter.wrapping_add(2);
+        let depth = chip8.stack_pointer;
+        self.run(chip8, max_cycles, |chip8| {
+            chip8.program_counter == return_address && chip8.stack_pointer == depth
+        })
+    }
+
+    /// Runs until the current subroutine returns through 00EE
+    pub fn step_out(&mut self, chip8: &mut Chip8, max_cycles: u64) -> StopReason {
+        let depth = chip8.stack_pointer;
+        if depth == 0 {
+            return StopReason::NotInSubroutine;
+        }
+        self.run(chip8, max_cycles, |chip8| chip8.stack_pointer < depth)
+    }
+
+    fn run(&mut self, chip8: &mut Chip8, max_cycles: u64, done: impl Fn(&Chip8) -> bool) -> StopReason {
+        for executed in 0..max_cycles {
+            if chip8.halted {
+                return StopReason::Halted;
+            }
+            if executed > 0 {
+                if let Some(reason) = self.pending_stop(chip8) {
+                    return reason;
+                }
+            }
+            let watch_hit = self.watch_hit(chip8);
+            if let Err(err) = chip8.cycle() {
+                return StopReason::Error(err);
+            }
+            self.cycles += 1;
+            if self.cycles.is_multiple_of(self.cycles_per_frame as u64) {
+                chip8.update_timers();
+            }
+            if let Some(reason) = watch_hit {
+                return reason;
+            }
+            if done(chip8) {
+                return StopReason::Stepped;
+            }
+        }
+        StopReason::CycleLimit
+    }
+
+    /// Returns the breakpoint that would stop execution before the next instruction, if any.
+    /// Callers that resume in slices use this to honour a stop on the first instruction of a slice.
+    pub fn pending_stop(&self, chip8: &Chip8) -> Option<StopReason> {
+        let pc = chip8.program_counter;
+        if self.breakpoints.contains(&pc) {
+            return Some(StopReason::Breakpoint { pc });
+        }
+
+        let opcode = next_opcode(chip8)?;
+        let instruction = Instruction::decode(opcode).ok()?;
+        if self
+            .opcode_breakpoints
+            .iter()
+            .any(|breakpoint| opcode & breakpoint.mask == breakpoint.value)
+        {
+            return Some(StopReason::Opcode { pc, instruction });
+        }
+        None
+    }
+
+    /// Returns the watchpoint the next instruction will trigger, if any
+    fn watch_hit(&self, chip8: &Chip8) -> Option<StopReason> {
+        if self.watchpoints.is_empty() {
+            return None;
+        }
+        let pc = chip8.program_counter;
+        let instruction = next_instruction(chip8)?;
+        let (range, access) = memory_access(chip8, instruction)?;
+        range.into_iter().find_map(|addr| {
+            let addr = addr as u16;
+            self.watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.kind.matches(access) && watchpoint.range.contains(&addr))
+                .then_some(StopReason::Watchpoint { pc, addr, access })
+        })
+    }
+}
+
+fn next_opcode(chip8: &Chip8) -> Option<u16> {
+    let pc = chip8.program_counter as usize;
+    let bytes = chip8.memory.get(pc..pc + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn next_instruction(chip8: &Chip8) -> Option<Instruction> {
+    Instruction::decode(next_opcode(chip8)?).ok()
+}
+
+/// Works out which memory an instruction will read or write, given the
+/// state it executes in. Instruction fetches are not counted.
+pub(crate) fn memory_access(chip8: &Chip8, instruction: Instruction) -> Option<(RangeInclusive<usize>, Access)> {
+    let i = chip8.index_register as usize;
+    let (length, access) = match instruction {
+        Instruction::Draw { n, .. } => {
+            let sprite_bytes = if n == 0 && chip8.has_schip_instructions() { 32 } else { n as usize };
+            (sprite_bytes * chip8.selected_planes.count_ones() as usize, Access::Read)
+        }
+        Instruction::StoreBcd { .. } => (3, Access::Write),
+        Instruction::StoreRegisters { x } => (x as usize + 1, Access::Write),
+        Instruction::LoadRegisters { x } => (x as usize + 1, Access::Read),
+        Instruction::SaveRange { x, y } => (x.abs_diff(y) as usize + 1, Access::Write),
+        Instruction::LoadRange { x, y } => (x.abs_diff(y) as usize + 1, Access::Read),
+        Instruction::LoadAudioPattern => (AUDIO_PATTERN_SIZE, Access::Read),
+        _ => return None,
+    };
+    (length > 0).then(|| (i..=i + length - 1, access))
+}
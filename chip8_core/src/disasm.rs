@@ -0,0 +1,240 @@
+use crate::{Instruction, Platform};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Longest jump table followed after a BNNN instruction
+const MAX_JUMP_TABLE_ENTRIES: usize = 256;
+/// Data bytes printed per `db` line
+const BYTES_PER_LINE: usize = 6;
+/// Column the address comments are aligned to
+const COMMENT_COLUMN: usize = 40;
+
+/// What a label marks, from the weakest to the strongest evidence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    /// Referenced by ANNN or F000 NNNN
+    Data,
+    /// Target of a jump
+    Code,
+    /// Target of a call
+    Subroutine,
+}
+
+/// A ROM split into code and data by following its control flow.
+///
+/// Tracing starts at the load address and follows every jump, call, skip and
+/// BNNN target. Bytes never reached as an instruction are treated as data.
+/// The `Display` implementation prints a labelled listing that assembles back
+/// into the original bytes.
+pub struct Disassembly {
+    origin: u16,
+    bytes: Vec<u8>,
+    instructions: BTreeMap<u16, Instruction>,
+    covered: Vec<bool>,
+    labels: BTreeMap<u16, LabelKind>,
+}
+
+/// Traces `rom` loaded at `origin`, decoding only instructions that `platform` supports
+pub fn disassemble(rom: &[u8], origin: u16, platform: Platform) -> Disassembly {
+    let mut disassembly = Disassembly {
+        origin,
+        bytes: rom.to_vec(),
+        instructions: BTreeMap::new(),
+        covered: vec![false; rom.len()],
+        labels: BTreeMap::new(),
+    };
+    disassembly.trace(platform);
+    disassembly
+}
+
+impl Disassembly {
+    /// Returns true if the byte at `addr` was reached as part of an instruction
+    pub fn is_code(&self, addr: u16) -> bool {
+        self.offset(addr).is_some_and(|offset| self.covered[offset])
+    }
+
+    /// Returns the instruction that starts at `addr`, if any
+    pub fn instruction_at(&self, addr: u16) -> Option<Instruction> {
+        self.instructions.get(&addr).copied()
+    }
+
+    /// Returns the label the listing uses for `addr`, if it has one
+    pub fn label(&self, addr: u16) -> Option<String> {
+        let kind = self.labels.get(&addr)?;
+        let offset = self.offset(addr)?;
+        if self.covered[offset] && !self.instructions.contains_key(&addr) {
+            // Points into the middle of an instruction, so there is no line to put it on
+            return None;
+        }
+        let prefix = match kind {
+            LabelKind::Data => "data",
+            LabelKind::Code => "loc",
+            LabelKind::Subroutine => "sub",
+        };
+        Some(format!("{}_{:03X}", prefix, addr))
+    }
+
+    fn offset(&self, addr: u16) -> Option<usize> {
+        let offset = (addr as usize).checked_sub(self.origin as usize)?;
+        (offset < self.bytes.len()).then_some(offset)
+    }
+
+    fn word_at(&self, addr: u32) -> Option<u16> {
+        let offset = (addr as usize).checked_sub(self.origin as usize)?;
+        let bytes = self.bytes.get(offset..offset + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn decode_at(&self, addr: u32, platform: Platform) -> Option<Instruction> {
+        let instruction = Instruction::decode(self.word_at(addr)?).ok()?;
+        platform.supports(instruction.platform()).then_some(instruction)
+    }
+
+    fn add_label(&mut self, addr: u16, kind: LabelKind) {
+        let label = self.labels.entry(addr).or_insert(kind);
+        *label = (*label).max(kind);
+    }
+
+    fn trace(&mut self, platform: Platform) {
+        let mut pending = vec![self.origin as u32];
+        while let Some(addr) = pending.pop() {
+            if addr > u16::MAX as u32 || self.instructions.contains_key(&(addr as u16)) {
+                continue;
+            }
+            let Some(instruction) = self.decode_at(addr, platform) else {
+                continue;
+            };
+            let size = instruction_size(instruction);
+            let start = addr as usize - self.origin as usize;
+            match self.covered.get(start..start + size) {
+                Some(bytes) if bytes.iter().all(|covered| !covered) => {}
+                // Runs off the end of the ROM or overlaps another instruction
+                _ => continue,
+            }
+            self.covered[start..start + size].fill(true);
+            self.instructions.insert(addr as u16, instruction);
+
+            let next = addr + size as u32;
+            match instruction {
+                Instruction::Jump { addr: target } => {
+                    self.add_label(target, LabelKind::Code);
+                    pending.push(target as u32);
+                }
+                Instruction::Call { addr: target } => {
+                    self.add_label(target, LabelKind::Subroutine);
+                    pending.push(target as u32);
+                    pending.push(next);
+                }
+                Instruction::JumpOffset { addr: target } => {
+                    // V0 is unknown, so follow the table of jumps that usually sits at NNN
+                    self.add_label(target, LabelKind::Code);
+                    for entry in 0..MAX_JUMP_TABLE_ENTRIES as u32 {
+                        let entry_addr = target as u32 + entry * 2;
+                        if entry > 0 && !matches!(self.decode_at(entry_addr, platform), Some(Instruction::Jump { .. })) {
+                            break;
+                        }
+                        pending.push(entry_addr);
+                    }
+                }
+                Instruction::Return | Instruction::Exit => {}
+                Instruction::SkipIfEqual { .. }
+                | Instruction::SkipIfNotEqual { .. }
+                | Instruction::SkipIfRegistersEqual { .. }
+                | Instruction::SkipIfRegistersNotEqual { .. }
+                | Instruction::SkipIfKeyPressed { .. }
+                | Instruction::SkipIfKeyNotPressed { .. } => {
+                    let skipped = match self.decode_at(next, platform) {
+                        Some(skipped) => instruction_size(skipped) as u32,
+                        None => 2,
+                    };
+                    pending.push(next);
+                    pending.push(next + skipped);
+                }
+                Instruction::LoadIndex { addr: target } => {
+                    if self.offset(target).is_some() {
+                        self.add_label(target, LabelKind::Data);
+                    }
+                    pending.push(next);
+                }
+                Instruction::LoadLongIndex => {
+                    let target = self.word_at(addr + 2).unwrap_or_default();
+                    if self.offset(target).is_some() {
+                        self.add_label(target, LabelKind::Data);
+                    }
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            }
+        }
+    }
+
+    /// Formats an address operand as its label, or as hex if it has none
+    fn address_operand(&self, addr: u16, digits: usize) -> String {
+        self.label(addr).unwrap_or_else(|| format!("{:#0width$X}", addr, width = digits + 2))
+    }
+
+    fn format_instruction(&self, addr: u16, instruction: Instruction) -> String {
+        match instruction {
+            Instruction::Jump { addr: target } => format!("JP {}", self.address_operand(target, 3)),
+            Instruction::Call { addr: target } => format!("CALL {}", self.address_operand(target, 3)),
+            Instruction::LoadIndex { addr: target } => format!("LD I, {}", self.address_operand(target, 3)),
+            Instruction::JumpOffset { addr: target } => format!("JP V0, {}", self.address_operand(target, 3)),
+            Instruction::LoadLongIndex => {
+                let target = self.word_at(addr as u32 + 2).unwrap_or_default();
+                format!("LD I, LONG {}", self.address_operand(target, 4))
+            }
+            _ => instruction.to_string(),
+        }
+    }
+}
+
+/// Bytes taken by an instruction, counting the address word after F000
+fn instruction_size(instruction: Instruction) -> usize {
+    match instruction {
+        Instruction::LoadLongIndex => 4,
+        _ => 2,
+    }
+}
+
+/// Writes one listing line with the address and raw bytes as a trailing comment
+fn write_line(f: &mut fmt::Formatter<'_>, text: &str, addr: usize, bytes: &[u8]) -> fmt::Result {
+    let raw: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+    writeln!(f, "    {:<width$}; {:03X}: {}", text, addr, raw.join(" "), width = COMMENT_COLUMN - 4)
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "    org {:#05X}", self.origin)?;
+
+        let mut offset = 0;
+        while offset < self.bytes.len() {
+            let addr = self.origin as usize + offset;
+            if let Some(label) = self.label(addr as u16) {
+                writeln!(f, "{}:", label)?;
+            }
+
+            if let Some(&instruction) = self.instructions.get(&(addr as u16)) {
+                let size = instruction_size(instruction);
+                let text = self.format_instruction(addr as u16, instruction);
+                write_line(f, &text, addr, &self.bytes[offset..offset + size])?;
+                offset += size;
+                continue;
+            }
+
+            // Data runs until the next instruction or label, or the end of the line
+            let mut end = offset + 1;
+            while end < self.bytes.len()
+                && end - offset < BYTES_PER_LINE
+                && !self.covered[end]
+                && self.label((self.origin as usize + end) as u16).is_none()
+            {
+                end += 1;
+            }
+            let data = &self.bytes[offset..end];
+            let values: Vec<String> = data.iter().map(|byte| format!("{:#04X}", byte)).collect();
+            write_line(f, &format!("db {}", values.join(", ")), addr, data)?;
+            offset = end;
+        }
+        Ok(())
+    }
+}
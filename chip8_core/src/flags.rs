@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+/// Number of RPL user flag registers available to FX75/FX85
+pub const RPL_FLAG_COUNT: usize = 16;
+
+/// Persists the RPL user flags saved by FX75 so they survive between runs.
+///
+/// Flags are keyed by [`Chip8::rom_hash`](crate::Chip8::rom_hash), so every
+/// ROM keeps its own set.
+pub trait FlagStorage {
+    /// Returns the flags previously saved for the ROM with the given hash
+    fn load_flags(&mut self, rom_hash: u64) -> Option<[u8; RPL_FLAG_COUNT]>;
+
+    /// Stores the flags for the ROM with the given hash
+    fn save_flags(&mut self, rom_hash: u64, flags: &[u8; RPL_FLAG_COUNT]);
+}
+
+/// Keeps flags in memory for the lifetime of the storage object
+#[derive(Debug, Default)]
+pub struct MemoryFlagStorage {
+    flags: HashMap<u64, [u8; RPL_FLAG_COUNT]>,
+}
+
+impl FlagStorage for MemoryFlagStorage {
+    fn load_flags(&mut self, rom_hash: u64) -> Option<[u8; RPL_FLAG_COUNT]> {
+        self.flags.get(&rom_hash).copied()
+    }
+
+    fn save_flags(&mut self, rom_hash: u64, flags: &[u8; RPL_FLAG_COUNT]) {
+        self.flags.insert(rom_hash, *flags);
+    }
+}
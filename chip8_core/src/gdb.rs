@@ -0,0 +1,472 @@
+use crate::{Access, Chip8, Chip8Error, Debugger, StopReason, WatchKind};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+/// Registers exposed to the debugger: V0-VF, I, PC, SP, DT and ST
+const GDB_REGISTER_COUNT: usize = 21;
+/// Register number of the program counter
+const PC_REGISTER: usize = 17;
+/// Largest packet the debugger may send or ask for, advertised in `qSupported`
+const PACKET_SIZE: usize = 0x4000;
+/// Cycles run between checks for an interrupt from the debugger
+const CYCLES_PER_SLICE: u64 = 10_000;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+/// Target description sent to the debugger in reply to `qXfer:features:read`
+pub const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>chip8</architecture>
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+/// Serves one GDB remote serial protocol session over `stream`.
+///
+/// Supports register and memory reads and writes, software and hardware
+/// breakpoints (`Z0`/`Z1`), watchpoints (`Z2`-`Z4`), single-step, continue
+/// and interrupting a running target with Ctrl-C. Returns when the debugger
+/// detaches, kills the target or disconnects.
+pub fn serve(stream: TcpStream, chip8: &mut Chip8, debugger: &mut Debugger) -> io::Result<()> {
+    // Acks and replies are tiny writes that would otherwise wait on delayed ACKs
+    stream.set_nodelay(true)?;
+    let mut session = Session {
+        stream,
+        chip8,
+        debugger,
+        pending: Vec::new(),
+        last_packet: Vec::new(),
+        no_ack: false,
+        last_stop: format!("S{:02x}", SIGTRAP),
+    };
+    while let Some(incoming) = session.read_packet()? {
+        let keep_going = match incoming {
+            Incoming::Packet(packet) => session.handle(&packet)?,
+            Incoming::Interrupt => {
+                session.last_stop = format!("S{:02x}", SIGINT);
+                let stop = session.last_stop.clone();
+                session.send(&stop)?;
+                true
+            }
+        };
+        if !keep_going {
+            break;
+        }
+    }
+    Ok(())
+}
+
+enum Incoming {
+    Packet(String),
+    Interrupt,
+}
+
+struct Session<'a> {
+    stream: TcpStream,
+    chip8: &'a mut Chip8,
+    debugger: &'a mut Debugger,
+    /// Bytes read while polling for an interrupt, to be handled next
+    pending: Vec<u8>,
+    last_packet: Vec<u8>,
+    no_ack: bool,
+    last_stop: String,
+}
+
+impl Session<'_> {
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        if !self.pending.is_empty() {
+            return Ok(Some(self.pending.remove(0)));
+        }
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    fn read_packet(&mut self) -> io::Result<Option<Incoming>> {
+        loop {
+            let Some(byte) = self.read_byte()? else {
+                return Ok(None);
+            };
+            match byte {
+                b'$' => {}
+                0x03 => return Ok(Some(Incoming::Interrupt)),
+                b'-' => {
+                    let packet = self.last_packet.clone();
+                    self.stream.write_all(&packet)?;
+                    continue;
+                }
+                _ => continue,
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let mut checksum = [0; 2];
+            for digit in &mut checksum {
+                *digit = self.read_byte()?.unwrap_or(0);
+            }
+
+            let expected = std::str::from_utf8(&checksum).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            let valid = expected == Some(checksum_of(&data));
+            if !self.no_ack {
+                self.stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(Some(Incoming::Packet(String::from_utf8_lossy(&data).into_owned())));
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes())).into_bytes();
+        self.stream.write_all(&packet)?;
+        self.stream.flush()?;
+        self.last_packet = packet;
+        Ok(())
+    }
+
+    /// Checks for Ctrl-C from the debugger without blocking
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let mut byte = [0];
+        let result = self.stream.read(&mut byte);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Ok(true),
+            Ok(_) if byte[0] == 0x03 => Ok(true),
+            Ok(_) => {
+                self.pending.push(byte[0]);
+                Ok(false)
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Handles one packet, returning false when the session should end
+    fn handle(&mut self, packet: &str) -> io::Result<bool> {
+        let (command, args) = packet.split_at(packet.len().min(1));
+        let reply = match command {
+            "?" => self.last_stop.clone(),
+            "g" => (0..GDB_REGISTER_COUNT).map(|n| self.register_hex(n)).collect(),
+            "G" => self.write_registers(args),
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(n) if n < GDB_REGISTER_COUNT => self.register_hex(n),
+                _ => "E01".to_string(),
+            },
+            "P" => match args.split_once('=').and_then(|(n, value)| Some((usize::from_str_radix(n, 16).ok()?, value))) {
+                Some((n, value)) if n < GDB_REGISTER_COUNT => match decode_hex(value) {
+                    Some(bytes) => {
+                        self.set_register(n, &bytes);
+                        "OK".to_string()
+                    }
+                    None => "E01".to_string(),
+                },
+                _ => "E01".to_string(),
+            },
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "Z" | "z" => self.breakpoint(command == "Z", args),
+            "s" | "c" => {
+                if let Ok(addr) = u16::from_str_radix(args, 16) {
+                    self.chip8.set_program_counter(addr);
+                }
+                let reason = if command == "s" {
+                    self.debugger.step(self.chip8)
+                } else {
+                    match self.resume()? {
+                        Some(reason) => reason,
+                        None => {
+                            self.last_stop = format!("S{:02x}", SIGINT);
+                            let stop = self.last_stop.clone();
+                            self.send(&stop)?;
+                            return Ok(true);
+                        }
+                    }
+                };
+                self.last_stop = stop_reply(&reason);
+                self.last_stop.clone()
+            }
+            "k" => return Ok(false),
+            "D" => {
+                self.send("OK")?;
+                return Ok(false);
+            }
+            "H" | "T" => "OK".to_string(),
+            "q" | "Q" => self.query(packet),
+            "v" if packet == "vKill" || packet.starts_with("vKill;") => {
+                self.send("OK")?;
+                return Ok(false);
+            }
+            _ => String::new(),
+        };
+        self.send(&reply)?;
+        Ok(true)
+    }
+
+    /// Runs until a stop, returning `None` if the debugger interrupted
+    fn resume(&mut self) -> io::Result<Option<StopReason>> {
+        let mut first_slice = true;
+        loop {
+            if !first_slice {
+                if let Some(reason) = self.debugger.pending_stop(self.chip8) {
+                    return Ok(Some(reason));
+                }
+            }
+            first_slice = false;
+            let reason = self.debugger.resume(self.chip8, CYCLES_PER_SLICE);
+            if reason != StopReason::CycleLimit {
+                return Ok(Some(reason));
+            }
+            if self.interrupted()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!("PacketSize={:x};qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+", PACKET_SIZE);
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, length)) = range.split_once(',') else {
+                return "E01".to_string();
+            };
+            let (Ok(offset), Ok(length)) = (usize::from_str_radix(offset, 16), usize::from_str_radix(length, 16)) else {
+                return "E01".to_string();
+            };
+            let start = offset.min(TARGET_XML.len());
+            let end = (start + length).min(TARGET_XML.len());
+            let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+            return format!("{}{}", marker, &TARGET_XML[start..end]);
+        }
+        if let Some(n) = packet.strip_prefix("qRegisterInfo") {
+            return match usize::from_str_radix(n, 16) {
+                Ok(n) if n < GDB_REGISTER_COUNT => register_info(n),
+                _ => "E45".to_string(),
+            };
+        }
+        match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Returns a register as little-endian hex
+    fn register_hex(&self, n: usize) -> String {
+        let value = match n {
+            0..=15 => self.chip8.register(n).into(),
+            16 => self.chip8.index_register(),
+            PC_REGISTER => self.chip8.program_counter(),
+            18 => self.chip8.stack_pointer(),
+            19 => self.chip8.delay_timer().into(),
+            _ => self.chip8.sound_timer().into(),
+        };
+        encode_hex(&value.to_le_bytes()[..register_size(n)])
+    }
+
+    /// Sets a register from little-endian bytes. The stack pointer is read-only.
+    fn set_register(&mut self, n: usize, bytes: &[u8]) {
+        let byte = bytes.first().copied().unwrap_or(0);
+        let word = u16::from_le_bytes([byte, bytes.get(1).copied().unwrap_or(0)]);
+        match n {
+            0..=15 => self.chip8.set_register(n, byte),
+            16 => self.chip8.set_index_register(word),
+            PC_REGISTER => self.chip8.set_program_counter(word),
+            19 => self.chip8.set_delay_timer(byte),
+            20 => self.chip8.set_sound_timer(byte),
+            _ => {}
+        }
+    }
+
+    fn write_registers(&mut self, hex: &str) -> String {
+        let Some(bytes) = decode_hex(hex) else {
+            return "E01".to_string();
+        };
+        let mut offset = 0;
+        for n in 0..GDB_REGISTER_COUNT {
+            let size = register_size(n);
+            let Some(value) = bytes.get(offset..offset + size) else {
+                break;
+            };
+            self.set_register(n, value);
+            offset += size;
+        }
+        "OK".to_string()
+    }
+
+    fn read_memory(&self, args: &str) -> String {
+        let Some((addr, length)) = parse_range(args) else {
+            return "E01".to_string();
+        };
+        let memory = self.chip8.memory();
+        if addr >= memory.len() {
+            return "E01".to_string();
+        }
+        // Each byte takes two hex digits in the reply
+        let end = addr.saturating_add(length.min(PACKET_SIZE / 2)).min(memory.len());
+        encode_hex(&memory[addr..end])
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let Some((range, data)) = args.split_once(':') else {
+            return "E01".to_string();
+        };
+        let (Some((addr, length)), Some(bytes)) = (parse_range(range), decode_hex(data)) else {
+            return "E01".to_string();
+        };
+        if bytes.len() != length {
+            return "E01".to_string();
+        }
+        match self.chip8.write_memory(addr, &bytes) {
+            Ok(()) => "OK".to_string(),
+            Err(_) => "E01".to_string(),
+        }
+    }
+
+    /// Inserts or removes a breakpoint or watchpoint from a `Z`/`z` packet
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut fields = args.split(',');
+        let (Some(kind), Some(addr), Some(length)) = (fields.next(), fields.next(), fields.next()) else {
+            return "E01".to_string();
+        };
+        let (Ok(addr), Ok(length)) = (u16::from_str_radix(addr, 16), u16::from_str_radix(length, 16)) else {
+            return "E01".to_string();
+        };
+        let watch = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.debugger.add_breakpoint(addr);
+                } else {
+                    self.debugger.remove_breakpoint(addr);
+                }
+                return "OK".to_string();
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return String::new(),
+        };
+        let range = addr..=addr.saturating_add(length.max(1) - 1);
+        if insert {
+            self.debugger.add_watchpoint(range, watch);
+        } else {
+            self.debugger.remove_watchpoint_range(range, watch);
+        }
+        "OK".to_string()
+    }
+}
+
+fn register_size(n: usize) -> usize {
+    match n {
+        16 | PC_REGISTER => 2,
+        _ => 1,
+    }
+}
+
+fn register_name(n: usize) -> String {
+    match n {
+        0..=15 => format!("v{:x}", n),
+        16 => "i".to_string(),
+        PC_REGISTER => "pc".to_string(),
+        18 => "sp".to_string(),
+        19 => "dt".to_string(),
+        _ => "st".to_string(),
+    }
+}
+
+/// Describes a register for LLDB, which asks with `qRegisterInfo` instead of reading the XML
+fn register_info(n: usize) -> String {
+    let offset: usize = (0..n).map(register_size).sum();
+    let generic = if n == PC_REGISTER { "generic:pc;" } else { "" };
+    format!(
+        "name:{};bitsize:{};offset:{};encoding:uint;format:hex;set:General Purpose Registers;{}",
+        register_name(n),
+        register_size(n) * 8,
+        offset,
+        generic
+    )
+}
+
+fn stop_reply(reason: &StopReason) -> String {
+    match reason {
+        StopReason::Stepped
+        | StopReason::Opcode { .. }
+        | StopReason::CycleLimit
+        | StopReason::NotInSubroutine => format!("S{:02x}", SIGTRAP),
+        StopReason::Breakpoint { .. } => format!("T{:02x}swbreak:;", SIGTRAP),
+        StopReason::Watchpoint { addr, access, .. } => {
+            let kind = match access {
+                Access::Read => "rwatch",
+                Access::Write => "watch",
+            };
+            format!("T{:02x}{}:{:x};", SIGTRAP, kind, addr)
+        }
+        StopReason::Halted => "W00".to_string(),
+        StopReason::Error(Chip8Error::InvalidOpcode { .. }) => format!("S{:02x}", SIGILL),
+        StopReason::Error(_) => format!("S{:02x}", SIGSEGV),
+    }
+}
+
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (addr, length) = text.split_once(',')?;
+    Some((usize::from_str_radix(addr, 16).ok()?, usize::from_str_radix(length, 16).ok()?))
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,102 @@
+use crate::{crc32, Framebuffer};
+
+/// Colours for the four 2-bit pixel values, matching the desktop frontend
+pub const PALETTE: [[u8; 3]; 4] = [[0, 0, 0], [255, 255, 255], [170, 170, 170], [85, 85, 85]];
+/// Characters for the four 2-bit pixel values in [`encode_ascii`]
+pub const ASCII_GLYPHS: [char; 4] = ['.', '#', '+', '@'];
+
+/// One line of text per row, one character per pixel
+pub fn encode_ascii(framebuffer: &Framebuffer) -> Vec<u8> {
+    let mut text = String::with_capacity((framebuffer.width + 1) * framebuffer.height);
+    for row in framebuffer.pixels.chunks(framebuffer.width) {
+        text.extend(row.iter().map(|&pixel| ASCII_GLYPHS[pixel as usize & 0x3]));
+        text.push('\n');
+    }
+    text.into_bytes()
+}
+
+/// Binary PBM, with every lit pixel black whichever plane drew it
+pub fn encode_pbm(framebuffer: &Framebuffer, scale: usize) -> Vec<u8> {
+    let width = framebuffer.width * scale;
+    let mut image = format!("P4\n{} {}\n", width, framebuffer.height * scale).into_bytes();
+    for row in framebuffer.pixels.chunks(framebuffer.width) {
+        let mut packed = vec![0u8; width.div_ceil(8)];
+        for x in 0..width {
+            if row[x / scale] != 0 {
+                packed[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        for _ in 0..scale {
+            image.extend_from_slice(&packed);
+        }
+    }
+    image
+}
+
+/// Indexed-colour PNG using [`PALETTE`], compressed with stored deflate blocks only
+pub fn encode_png(framebuffer: &Framebuffer, scale: usize) -> Vec<u8> {
+    let width = framebuffer.width * scale;
+    let height = framebuffer.height * scale;
+    let mut raw = Vec::with_capacity((width + 1) * height);
+    for row in framebuffer.pixels.chunks(framebuffer.width) {
+        // Each scanline starts with filter type 0: the row is stored as is
+        let scanline: Vec<u8> = std::iter::once(0)
+            .chain((0..width).map(|x| row[x / scale] & 0x3))
+            .collect();
+        for _ in 0..scale {
+            raw.extend_from_slice(&scanline);
+        }
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth 8, colour type 3 (indexed), default compression, filtering and no interlace
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"PLTE", PALETTE.as_flattened());
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps data in a zlib stream made of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xFFFF;
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let length = block.len() as u16;
+        stream.push(is_final as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % MODULUS;
+        b = (b + a) % MODULUS;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,539 @@
+use crate::Platform;
+use std::fmt;
+use std::str::FromStr;
+
+/// A decoded CHIP-8, SUPER-CHIP or XO-CHIP instruction.
+///
+/// `x` and `y` are register indices, `byte` is an 8-bit immediate, `addr` a
+/// 12-bit address and `n` a 4-bit immediate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// 0000 - do nothing
+    Nop,
+    /// 00E0 - clear the selected planes of the display
+    ClearScreen,
+    /// 00EE - return from a subroutine
+    Return,
+    /// 00CN - scroll the display down N rows
+    ScrollDown { n: u8 },
+    /// 00DN - scroll the display up N rows
+    ScrollUp { n: u8 },
+    /// 00FB - scroll the display right 4 pixels
+    ScrollRight,
+    /// 00FC - scroll the display left 4 pixels
+    ScrollLeft,
+    /// 00FD - exit the interpreter
+    Exit,
+    /// 00FE - switch to low resolution
+    LowRes,
+    /// 00FF - switch to high resolution
+    HighRes,
+    /// 1NNN - jump to NNN
+    Jump { addr: u16 },
+    /// 2NNN - call the subroutine at NNN
+    Call { addr: u16 },
+    /// 3XNN - skip the next instruction if VX == NN
+    SkipIfEqual { x: u8, byte: u8 },
+    /// 4XNN - skip the next instruction if VX != NN
+    SkipIfNotEqual { x: u8, byte: u8 },
+    /// 5XY0 - skip the next instruction if VX == VY
+    SkipIfRegistersEqual { x: u8, y: u8 },
+    /// 5XY2 - store VX to VY in memory at I
+    SaveRange { x: u8, y: u8 },
+    /// 5XY3 - load VX to VY from memory at I
+    LoadRange { x: u8, y: u8 },
+    /// 6XNN - VX = NN
+    LoadByte { x: u8, byte: u8 },
+    /// 7XNN - VX += NN
+    AddByte { x: u8, byte: u8 },
+    /// 8XY0 - VX = VY
+    Move { x: u8, y: u8 },
+    /// 8XY1 - VX |= VY
+    Or { x: u8, y: u8 },
+    /// 8XY2 - VX &= VY
+    And { x: u8, y: u8 },
+    /// 8XY3 - VX ^= VY
+    Xor { x: u8, y: u8 },
+    /// 8XY4 - VX += VY, VF = carry
+    Add { x: u8, y: u8 },
+    /// 8XY5 - VX -= VY, VF = NOT borrow
+    Sub { x: u8, y: u8 },
+    /// 8XY6 - VX >>= 1, VF = shifted out bit
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7 - VX = VY - VX, VF = NOT borrow
+    SubReverse { x: u8, y: u8 },
+    /// 8XYE - VX <<= 1, VF = shifted out bit
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0 - skip the next instruction if VX != VY
+    SkipIfRegistersNotEqual { x: u8, y: u8 },
+    /// ANNN - I = NNN
+    LoadIndex { addr: u16 },
+    /// BNNN - jump to NNN + V0
+    JumpOffset { addr: u16 },
+    /// CXNN - VX = random byte & NN
+    Random { x: u8, byte: u8 },
+    /// DXYN - draw an N row sprite at (VX, VY)
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E - skip the next instruction if key VX is pressed
+    SkipIfKeyPressed { x: u8 },
+    /// EXA1 - skip the next instruction if key VX is not pressed
+    SkipIfKeyNotPressed { x: u8 },
+    /// F000 - I = the 16-bit address stored in the following word
+    LoadLongIndex,
+    /// FN01 - select the drawing planes, a mask of the two planes from 0 to 3
+    SelectPlanes { planes: u8 },
+    /// F002 - load the audio pattern from memory at I
+    LoadAudioPattern,
+    /// FX07 - VX = delay timer
+    LoadDelayTimer { x: u8 },
+    /// FX0A - wait for a key press and store it in VX
+    WaitForKey { x: u8 },
+    /// FX15 - delay timer = VX
+    SetDelayTimer { x: u8 },
+    /// FX18 - sound timer = VX
+    SetSoundTimer { x: u8 },
+    /// FX1E - I += VX
+    AddIndex { x: u8 },
+    /// FX29 - I = address of the small font glyph for VX
+    LoadFont { x: u8 },
+    /// FX30 - I = address of the big font glyph for VX
+    LoadBigFont { x: u8 },
+    /// FX33 - store the BCD digits of VX in memory at I
+    StoreBcd { x: u8 },
+    /// FX3A - audio pitch = VX
+    SetPitch { x: u8 },
+    /// FX55 - store V0 to VX in memory at I
+    StoreRegisters { x: u8 },
+    /// FX65 - load V0 to VX from memory at I
+    LoadRegisters { x: u8 },
+    /// FX75 - store V0 to VX in the RPL user flags
+    StoreFlags { x: u8 },
+    /// FX85 - load V0 to VX from the RPL user flags
+    LoadFlags { x: u8 },
+}
+
+/// The opcode does not correspond to any instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#06X} is not a valid opcode", self.opcode)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Instruction {
+    /// Decodes a 16-bit opcode
+    pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+        let nibble1 = (opcode & 0xF000) >> 12;
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let byte = (opcode & 0x00FF) as u8;
+        let addr = opcode & 0x0FFF;
+
+        let instruction = match (nibble1, x, y, n) {
+            (0, 0, 0, 0) => Instruction::Nop,
+            (0, 0, 0xE, 0) => Instruction::ClearScreen,
+            (0, 0, 0xE, 0xE) => Instruction::Return,
+            (0, 0, 0xC, _) => Instruction::ScrollDown { n },
+            (0, 0, 0xD, _) => Instruction::ScrollUp { n },
+            (0, 0, 0xF, 0xB) => Instruction::ScrollRight,
+            (0, 0, 0xF, 0xC) => Instruction::ScrollLeft,
+            (0, 0, 0xF, 0xD) => Instruction::Exit,
+            (0, 0, 0xF, 0xE) => Instruction::LowRes,
+            (0, 0, 0xF, 0xF) => Instruction::HighRes,
+            (1, _, _, _) => Instruction::Jump { addr },
+            (2, _, _, _) => Instruction::Call { addr },
+            (3, _, _, _) => Instruction::SkipIfEqual { x, byte },
+            (4, _, _, _) => Instruction::SkipIfNotEqual { x, byte },
+            (5, _, _, 0) => Instruction::SkipIfRegistersEqual { x, y },
+            (5, _, _, 2) => Instruction::SaveRange { x, y },
+            (5, _, _, 3) => Instruction::LoadRange { x, y },
+            (6, _, _, _) => Instruction::LoadByte { x, byte },
+            (7, _, _, _) => Instruction::AddByte { x, byte },
+            (8, _, _, 0) => Instruction::Move { x, y },
+            (8, _, _, 1) => Instruction::Or { x, y },
+            (8, _, _, 2) => Instruction::And { x, y },
+            (8, _, _, 3) => Instruction::Xor { x, y },
+            (8, _, _, 4) => Instruction::Add { x, y },
+            (8, _, _, 5) => Instruction::Sub { x, y },
+            (8, _, _, 6) => Instruction::ShiftRight { x, y },
+            (8, _, _, 7) => Instruction::SubReverse { x, y },
+            (8, _, _, 0xE) => Instruction::ShiftLeft { x, y },
+            (9, _, _, 0) => Instruction::SkipIfRegistersNotEqual { x, y },
+            (0xA, _, _, _) => Instruction::LoadIndex { addr },
+            (0xB, _, _, _) => Instruction::JumpOffset { addr },
+            (0xC, _, _, _) => Instruction::Random { x, byte },
+            (0xD, _, _, _) => Instruction::Draw { x, y, n },
+            (0xE, _, 9, 0xE) => Instruction::SkipIfKeyPressed { x },
+            (0xE, _, 0xA, 1) => Instruction::SkipIfKeyNotPressed { x },
+            (0xF, 0, 0, 0) => Instruction::LoadLongIndex,
+            (0xF, 0..=3, 0, 1) => Instruction::SelectPlanes { planes: x },
+            (0xF, 0, 0, 2) => Instruction::LoadAudioPattern,
+            (0xF, _, 0, 7) => Instruction::LoadDelayTimer { x },
+            (0xF, _, 0, 0xA) => Instruction::WaitForKey { x },
+            (0xF, _, 1, 5) => Instruction::SetDelayTimer { x },
+            (0xF, _, 1, 8) => Instruction::SetSoundTimer { x },
+            (0xF, _, 1, 0xE) => Instruction::AddIndex { x },
+            (0xF, _, 2, 9) => Instruction::LoadFont { x },
+            (0xF, _, 3, 0) => Instruction::LoadBigFont { x },
+            (0xF, _, 3, 3) => Instruction::StoreBcd { x },
+            (0xF, _, 3, 0xA) => Instruction::SetPitch { x },
+            (0xF, _, 5, 5) => Instruction::StoreRegisters { x },
+            (0xF, _, 6, 5) => Instruction::LoadRegisters { x },
+            (0xF, _, 7, 5) => Instruction::StoreFlags { x },
+            (0xF, _, 8, 5) => Instruction::LoadFlags { x },
+            _ => return Err(DecodeError { opcode }),
+        };
+        Ok(instruction)
+    }
+
+    /// Encodes the instruction back into its 16-bit opcode
+    pub fn encode(&self) -> u16 {
+        let xy = |base: u16, x: u8, y: u8, n: u16| base | ((x as u16 & 0xF) << 8) | ((y as u16 & 0xF) << 4) | n;
+        let xnn = |base: u16, x: u8, byte: u8| base | ((x as u16 & 0xF) << 8) | byte as u16;
+        let fx = |x: u8, low: u16| 0xF000 | ((x as u16 & 0xF) << 8) | low;
+
+        match *self {
+            Instruction::Nop => 0x0000,
+            Instruction::ClearScreen => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::ScrollDown { n } => 0x00C0 | (n as u16 & 0xF),
+            Instruction::ScrollUp { n } => 0x00D0 | (n as u16 & 0xF),
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowRes => 0x00FE,
+            Instruction::HighRes => 0x00FF,
+            Instruction::Jump { addr } => 0x1000 | (addr & 0x0FFF),
+            Instruction::Call { addr } => 0x2000 | (addr & 0x0FFF),
+            Instruction::SkipIfEqual { x, byte } => xnn(0x3000, x, byte),
+            Instruction::SkipIfNotEqual { x, byte } => xnn(0x4000, x, byte),
+            Instruction::SkipIfRegistersEqual { x, y } => xy(0x5000, x, y, 0),
+            Instruction::SaveRange { x, y } => xy(0x5000, x, y, 2),
+            Instruction::LoadRange { x, y } => xy(0x5000, x, y, 3),
+            Instruction::LoadByte { x, byte } => xnn(0x6000, x, byte),
+            Instruction::AddByte { x, byte } => xnn(0x7000, x, byte),
+            Instruction::Move { x, y } => xy(0x8000, x, y, 0),
+            Instruction::Or { x, y } => xy(0x8000, x, y, 1),
+            Instruction::And { x, y } => xy(0x8000, x, y, 2),
+            Instruction::Xor { x, y } => xy(0x8000, x, y, 3),
+            Instruction::Add { x, y } => xy(0x8000, x, y, 4),
+            Instruction::Sub { x, y } => xy(0x8000, x, y, 5),
+            Instruction::ShiftRight { x, y } => xy(0x8000, x, y, 6),
+            Instruction::SubReverse { x, y } => xy(0x8000, x, y, 7),
+            Instruction::ShiftLeft { x, y } => xy(0x8000, x, y, 0xE),
+            Instruction::SkipIfRegistersNotEqual { x, y } => xy(0x9000, x, y, 0),
+            Instruction::LoadIndex { addr } => 0xA000 | (addr & 0x0FFF),
+            Instruction::JumpOffset { addr } => 0xB000 | (addr & 0x0FFF),
+            Instruction::Random { x, byte } => xnn(0xC000, x, byte),
+            Instruction::Draw { x, y, n } => xy(0xD000, x, y, n as u16 & 0xF),
+            Instruction::SkipIfKeyPressed { x } => xnn(0xE000, x, 0x9E),
+            Instruction::SkipIfKeyNotPressed { x } => xnn(0xE000, x, 0xA1),
+            Instruction::LoadLongIndex => 0xF000,
+            Instruction::SelectPlanes { planes } => fx(planes, 0x01),
+            Instruction::LoadAudioPattern => 0xF002,
+            Instruction::LoadDelayTimer { x } => fx(x, 0x07),
+            Instruction::WaitForKey { x } => fx(x, 0x0A),
+            Instruction::SetDelayTimer { x } => fx(x, 0x15),
+            Instruction::SetSoundTimer { x } => fx(x, 0x18),
+            Instruction::AddIndex { x } => fx(x, 0x1E),
+            Instruction::LoadFont { x } => fx(x, 0x29),
+            Instruction::LoadBigFont { x } => fx(x, 0x30),
+            Instruction::StoreBcd { x } => fx(x, 0x33),
+            Instruction::SetPitch { x } => fx(x, 0x3A),
+            Instruction::StoreRegisters { x } => fx(x, 0x55),
+            Instruction::LoadRegisters { x } => fx(x, 0x65),
+            Instruction::StoreFlags { x } => fx(x, 0x75),
+            Instruction::LoadFlags { x } => fx(x, 0x85),
+        }
+    }
+
+    /// Returns the earliest platform that provides this instruction
+    pub fn platform(&self) -> Platform {
+        match self {
+            Instruction::ScrollDown { .. }
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::LowRes
+            | Instruction::HighRes
+            | Instruction::LoadBigFont { .. }
+            | Instruction::StoreFlags { .. }
+            | Instruction::LoadFlags { .. } => Platform::SuperChip,
+            Instruction::ScrollUp { .. }
+            | Instruction::SaveRange { .. }
+            | Instruction::LoadRange { .. }
+            | Instruction::LoadLongIndex
+            | Instruction::SelectPlanes { .. }
+            | Instruction::LoadAudioPattern
+            | Instruction::SetPitch { .. } => Platform::XoChip,
+            _ => Platform::Chip8,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Nop => write!(f, "NOP"),
+            Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollDown { n } => write!(f, "SCD {}", n),
+            Instruction::ScrollUp { n } => write!(f, "SCU {}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowRes => write!(f, "LOW"),
+            Instruction::HighRes => write!(f, "HIGH"),
+            Instruction::Jump { addr } => write!(f, "JP {:#05X}", addr),
+            Instruction::Call { addr } => write!(f, "CALL {:#05X}", addr),
+            Instruction::SkipIfEqual { x, byte } => write!(f, "SE V{:X}, {:#04X}", x, byte),
+            Instruction::SkipIfNotEqual { x, byte } => write!(f, "SNE V{:X}, {:#04X}", x, byte),
+            Instruction::SkipIfRegistersEqual { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange { x, y } => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            Instruction::LoadByte { x, byte } => write!(f, "LD V{:X}, {:#04X}", x, byte),
+            Instruction::AddByte { x, byte } => write!(f, "ADD V{:X}, {:#04X}", x, byte),
+            Instruction::Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipIfRegistersNotEqual { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadIndex { addr } => write!(f, "LD I, {:#05X}", addr),
+            Instruction::JumpOffset { addr } => write!(f, "JP V0, {:#05X}", addr),
+            Instruction::Random { x, byte } => write!(f, "RND V{:X}, {:#04X}", x, byte),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipIfKeyPressed { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipIfKeyNotPressed { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadLongIndex => write!(f, "LD I, LONG"),
+            Instruction::SelectPlanes { planes } => write!(f, "PLANE {}", planes),
+            Instruction::LoadAudioPattern => write!(f, "AUDIO"),
+            Instruction::LoadDelayTimer { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitForKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelayTimer { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSoundTimer { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIndex { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::LoadFont { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::LoadBigFont { x } => write!(f, "LD HF, V{:X}", x),
+            Instruction::StoreBcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::SetPitch { x } => write!(f, "PITCH V{:X}", x),
+            Instruction::StoreRegisters { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::LoadRegisters { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::StoreFlags { x } => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+/// One operand of an instruction written in assembly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// A general register, V0 to VF
+    Register(u8),
+    /// The index register, `I`
+    Index,
+    /// Memory addressed by the index register, `[I]`
+    IndirectIndex,
+    /// The delay timer, `DT`
+    DelayTimer,
+    /// The sound timer, `ST`
+    SoundTimer,
+    /// A key press, `K`
+    Key,
+    /// The small font, `F`
+    Font,
+    /// The big font, `HF`
+    BigFont,
+    /// BCD digits, `B`
+    Bcd,
+    /// The RPL user flags, `R`
+    Flags,
+    /// The 16-bit address following `LD I, LONG`
+    Long,
+    /// A number, such as an address, byte or nibble
+    Value(u16),
+}
+
+impl Operand {
+    /// Parses a register or keyword operand, returning `None` for anything else
+    pub fn keyword(text: &str) -> Option<Operand> {
+        let upper = text.trim().to_ascii_uppercase();
+        let operand = match upper.as_str() {
+            "I" => Operand::Index,
+            "[I]" => Operand::IndirectIndex,
+            "DT" => Operand::DelayTimer,
+            "ST" => Operand::SoundTimer,
+            "K" => Operand::Key,
+            "F" => Operand::Font,
+            "HF" => Operand::BigFont,
+            "B" => Operand::Bcd,
+            "R" => Operand::Flags,
+            "LONG" => Operand::Long,
+            _ => {
+                let digit = upper.strip_prefix('V').filter(|digit| digit.len() == 1)?;
+                Operand::Register(u8::from_str_radix(digit, 16).ok()?)
+            }
+        };
+        Some(operand)
+    }
+
+    /// Parses an operand, accepting keywords and decimal, `0x` hex or `0b` binary numbers
+    pub fn parse(text: &str) -> Option<Operand> {
+        Operand::keyword(text).or_else(|| parse_number(text).map(Operand::Value))
+    }
+}
+
+/// Parses a decimal, `0x` hexadecimal or `0b` binary number
+pub fn parse_number(text: &str) -> Option<u16> {
+    let text = text.trim();
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/// Errors raised when assembly text does not form an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The mnemonic is not known
+    UnknownMnemonic(String),
+    /// The mnemonic is known but does not take these operands
+    InvalidOperands(String),
+    /// A numeric operand does not fit its field
+    ValueOutOfRange { value: u16, max: u16 },
+    /// An operand is neither a keyword nor a number
+    InvalidOperand(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownMnemonic(mnemonic) => write!(f, "unknown mnemonic '{}'", mnemonic),
+            ParseError::InvalidOperands(mnemonic) => write!(f, "invalid operands for '{}'", mnemonic),
+            ParseError::ValueOutOfRange { value, max } => {
+                write!(f, "value {:#X} is out of range (maximum {:#X})", value, max)
+            }
+            ParseError::InvalidOperand(operand) => write!(f, "invalid operand '{}'", operand),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Instruction {
+    /// Builds an instruction from a mnemonic and its parsed operands, using the
+    /// syntax printed by the `Display` implementation
+    pub fn assemble(mnemonic: &str, operands: &[Operand]) -> Result<Instruction, ParseError> {
+        use Operand::*;
+
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let invalid = || ParseError::InvalidOperands(mnemonic.clone());
+        let fits = |value: u16, max: u16| {
+            if value <= max {
+                Ok(value)
+            } else {
+                Err(ParseError::ValueOutOfRange { value, max })
+            }
+        };
+        let addr = |value: u16| fits(value, 0xFFF);
+        let byte = |value: u16| fits(value, 0xFF).map(|value| value as u8);
+        let nibble = |value: u16| fits(value, 0xF).map(|value| value as u8);
+
+        let instruction = match (mnemonic.as_str(), operands) {
+            ("NOP", []) => Instruction::Nop,
+            ("CLS", []) => Instruction::ClearScreen,
+            ("RET", []) => Instruction::Return,
+            ("SCD", [Value(n)]) => Instruction::ScrollDown { n: nibble(*n)? },
+            ("SCU", [Value(n)]) => Instruction::ScrollUp { n: nibble(*n)? },
+            ("SCR", []) => Instruction::ScrollRight,
+            ("SCL", []) => Instruction::ScrollLeft,
+            ("EXIT", []) => Instruction::Exit,
+            ("LOW", []) => Instruction::LowRes,
+            ("HIGH", []) => Instruction::HighRes,
+            ("JP", [Value(a)]) => Instruction::Jump { addr: addr(*a)? },
+            ("JP", [Register(0), Value(a)]) => Instruction::JumpOffset { addr: addr(*a)? },
+            ("CALL", [Value(a)]) => Instruction::Call { addr: addr(*a)? },
+            ("SE", [Register(x), Value(b)]) => Instruction::SkipIfEqual { x: *x, byte: byte(*b)? },
+            ("SE", [Register(x), Register(y)]) => Instruction::SkipIfRegistersEqual { x: *x, y: *y },
+            ("SNE", [Register(x), Value(b)]) => Instruction::SkipIfNotEqual { x: *x, byte: byte(*b)? },
+            ("SNE", [Register(x), Register(y)]) => Instruction::SkipIfRegistersNotEqual { x: *x, y: *y },
+            ("SAVE", [Register(x), Register(y)]) => Instruction::SaveRange { x: *x, y: *y },
+            ("LOAD", [Register(x), Register(y)]) => Instruction::LoadRange { x: *x, y: *y },
+            ("LD", [Register(x), Value(b)]) => Instruction::LoadByte { x: *x, byte: byte(*b)? },
+            ("LD", [Register(x), Register(y)]) => Instruction::Move { x: *x, y: *y },
+            ("LD", [Index, Value(a)]) => Instruction::LoadIndex { addr: addr(*a)? },
+            ("LD", [Index, Long]) => Instruction::LoadLongIndex,
+            ("LD", [Register(x), DelayTimer]) => Instruction::LoadDelayTimer { x: *x },
+            ("LD", [Register(x), Key]) => Instruction::WaitForKey { x: *x },
+            ("LD", [DelayTimer, Register(x)]) => Instruction::SetDelayTimer { x: *x },
+            ("LD", [SoundTimer, Register(x)]) => Instruction::SetSoundTimer { x: *x },
+            ("LD", [Font, Register(x)]) => Instruction::LoadFont { x: *x },
+            ("LD", [BigFont, Register(x)]) => Instruction::LoadBigFont { x: *x },
+            ("LD", [Bcd, Register(x)]) => Instruction::StoreBcd { x: *x },
+            ("LD", [IndirectIndex, Register(x)]) => Instruction::StoreRegisters { x: *x },
+            ("LD", [Register(x), IndirectIndex]) => Instruction::LoadRegisters { x: *x },
+            ("LD", [Flags, Register(x)]) => Instruction::StoreFlags { x: *x },
+            ("LD", [Register(x), Flags]) => Instruction::LoadFlags { x: *x },
+            ("ADD", [Register(x), Value(b)]) => Instruction::AddByte { x: *x, byte: byte(*b)? },
+            ("ADD", [Register(x), Register(y)]) => Instruction::Add { x: *x, y: *y },
+            ("ADD", [Index, Register(x)]) => Instruction::AddIndex { x: *x },
+            ("OR", [Register(x), Register(y)]) => Instruction::Or { x: *x, y: *y },
+            ("AND", [Register(x), Register(y)]) => Instruction::And { x: *x, y: *y },
+            ("XOR", [Register(x), Register(y)]) => Instruction::Xor { x: *x, y: *y },
+            ("SUB", [Register(x), Register(y)]) => Instruction::Sub { x: *x, y: *y },
+            ("SUBN", [Register(x), Register(y)]) => Instruction::SubReverse { x: *x, y: *y },
+            ("SHR", [Register(x)]) => Instruction::ShiftRight { x: *x, y: *x },
+            ("SHR", [Register(x), Register(y)]) => Instruction::ShiftRight { x: *x, y: *y },
+            ("SHL", [Register(x)]) => Instruction::ShiftLeft { x: *x, y: *x },
+            ("SHL", [Register(x), Register(y)]) => Instruction::ShiftLeft { x: *x, y: *y },
+            ("RND", [Register(x), Value(b)]) => Instruction::Random { x: *x, byte: byte(*b)? },
+            ("DRW", [Register(x), Register(y), Value(n)]) => Instruction::Draw { x: *x, y: *y, n: nibble(*n)? },
+            ("SKP", [Register(x)]) => Instruction::SkipIfKeyPressed { x: *x },
+            ("SKNP", [Register(x)]) => Instruction::SkipIfKeyNotPressed { x: *x },
+            ("PLANE", [Value(planes)]) => Instruction::SelectPlanes { planes: fits(*planes, 3)? as u8 },
+            ("AUDIO", []) => Instruction::LoadAudioPattern,
+            ("PITCH", [Register(x)]) => Instruction::SetPitch { x: *x },
+            (
+                "NOP" | "CLS" | "RET" | "SCD" | "SCU" | "SCR" | "SCL" | "EXIT" | "LOW" | "HIGH" | "JP" | "CALL" | "SE"
+                | "SNE" | "SAVE" | "LOAD" | "LD" | "ADD" | "OR" | "AND" | "XOR" | "SUB" | "SUBN" | "SHR" | "SHL"
+                | "RND" | "DRW" | "SKP" | "SKNP" | "PLANE" | "AUDIO" | "PITCH",
+                _,
+            ) => return Err(invalid()),
+            _ => return Err(ParseError::UnknownMnemonic(mnemonic)),
+        };
+        Ok(instruction)
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = ParseError;
+
+    /// Parses one instruction in the syntax printed by the `Display` implementation,
+    /// such as `LD V3, 0x1F`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands = rest
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .map(|operand| Operand::parse(operand).ok_or_else(|| ParseError::InvalidOperand(operand.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+        Instruction::assemble(mnemonic, &operands)
+    }
+}
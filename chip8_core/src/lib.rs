@@ -1,13 +1,49 @@
-use rand::random;
+use std::fmt;
+use std::str::FromStr;
+
+mod audio;
+mod crc;
+mod debug;
+mod disasm;
+mod flags;
+mod gdb;
+mod image;
+mod instruction;
+mod quirks;
+mod rewind;
+mod rng;
+mod state;
+mod trace;
+
+pub use audio::{AUDIO_PATTERN_SIZE, DEFAULT_PITCH};
+pub use crc::crc32;
+pub use debug::{Access, Debugger, OpcodeBreakpoint, StopReason, WatchKind, DEFAULT_CYCLES_PER_FRAME};
+pub use disasm::{disassemble, Disassembly};
+pub use flags::{FlagStorage, MemoryFlagStorage, RPL_FLAG_COUNT};
+pub use gdb::{serve as serve_gdb, TARGET_XML};
+pub use image::{encode_ascii, encode_pbm, encode_png, ASCII_GLYPHS, PALETTE};
+pub use instruction::{parse_number, DecodeError, Instruction, Operand, ParseError};
+pub use quirks::Quirks;
+pub use rewind::RewindBuffer;
+pub use rng::{RandomSource, XorShiftRng, DEFAULT_SEED};
+pub use state::{StateError, STATE_VERSION};
+pub use trace::Tracer;
 
 pub const DISPLAY_WIDTH: usize = 64;
 pub const DISPLAY_HEIGHT: usize = 32;
+pub const HIRES_DISPLAY_WIDTH: usize = 128;
+pub const HIRES_DISPLAY_HEIGHT: usize = 64;
 pub const MEMORY_SIZE: usize = 4096;
+pub const XO_MEMORY_SIZE: usize = 0x10000;
 const REGISTER_COUNT: usize = 16;
 const STACK_DEPTH: usize = 16;
 const KEY_COUNT: usize = 16;
 pub const STARTING_ADDRESS: u16 = 0x200;
 const FONT_DATA_SIZE: usize = 80;
+const BIG_FONT_ADDRESS: usize = FONT_DATA_SIZE;
+const BIG_FONT_DATA_SIZE: usize = 160;
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
 
 const FONT_DATA: [u8; FONT_DATA_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -28,10 +64,194 @@ const FONT_DATA: [u8; FONT_DATA_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80  // F
 ];
 
+const BIG_FONT_DATA: [u8; BIG_FONT_DATA_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0  // F
+];
+
+/// Folds bytes into a 64-bit FNV-1a hash
+pub(crate) fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+    data.iter()
+        .fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Errors raised while executing a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// The opcode at `pc` does not decode to a known instruction
+    InvalidOpcode { pc: u16, opcode: u16 },
+    /// A subroutine call was made with the call stack already full
+    StackOverflow,
+    /// A return was executed with an empty call stack
+    StackUnderflow,
+    /// An instruction tried to access memory past the end of the address space
+    MemoryOutOfBounds { addr: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode {:#06X} at address {:#05X}", opcode, pc)
+            }
+            Chip8Error::StackOverflow => write!(f, "call stack overflow"),
+            Chip8Error::StackUnderflow => write!(f, "return with an empty call stack"),
+            Chip8Error::MemoryOutOfBounds { addr } => {
+                write!(f, "memory access out of bounds at address {:#X}", addr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+/// Describes where a program segment was placed in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadInfo {
+    /// Address of the first byte of the segment
+    pub start_address: usize,
+    /// Address one past the last byte of the segment
+    pub end_address: usize,
+    /// Number of bytes copied into memory
+    pub bytes_loaded: usize,
+    /// Number of bytes left between the end of the segment and the end of memory
+    pub bytes_remaining: usize,
+}
+
+/// Errors raised when a program does not fit into memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    /// The load address lies outside of memory
+    AddressOutOfRange { address: usize },
+    /// The segment is larger than the space left after its load address
+    TooLarge { size: usize, available: usize },
+    /// Two segments of the same load would write to the same address
+    Overlap { address: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::AddressOutOfRange { address } => {
+                write!(f, "load address {:#X} is outside of memory", address)
+            }
+            LoadError::TooLarge { size, available } => write!(
+                f,
+                "program size ({} bytes) exceeds available memory space ({} bytes)",
+                size, available
+            ),
+            LoadError::Overlap { address } => {
+                write!(f, "program segments overlap at address {:#X}", address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Instruction set variants the core can emulate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    /// The original CHIP-8 instruction set
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1, adding the 128x64 high resolution mode and scrolling
+    SuperChip,
+    /// XO-CHIP, extending SUPER-CHIP with 64 KiB of memory and new instructions
+    XoChip,
+}
+
+impl Platform {
+    /// Returns the quirks programs written for this platform usually expect
+    pub fn default_quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::default(),
+            Platform::SuperChip => Quirks::super_chip(),
+            Platform::XoChip => Quirks::xo_chip(),
+        }
+    }
+
+    /// Returns true if programs written for `required` run on this platform
+    pub fn supports(self, required: Platform) -> bool {
+        self.generation() >= required.generation()
+    }
+
+    /// Orders platforms so that each one extends the previous
+    fn generation(self) -> u8 {
+        match self {
+            Platform::Chip8 => 0,
+            Platform::SuperChip => 1,
+            Platform::XoChip => 2,
+        }
+    }
+
+    /// Returns the size of the address space on this platform
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => MEMORY_SIZE,
+            Platform::XoChip => XO_MEMORY_SIZE,
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = ParsePlatformError;
+
+    /// Parses the names the command line tools take: `chip8`, `schip` or `xochip`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "chip8" => Ok(Platform::Chip8),
+            "schip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(ParsePlatformError(name.to_string())),
+        }
+    }
+}
+
+/// Error returned when a platform name is not one of `chip8`, `schip` or `xochip`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePlatformError(pub String);
+
+impl fmt::Display for ParsePlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown platform '{}', expected chip8, schip or xochip", self.0)
+    }
+}
+
+impl std::error::Error for ParsePlatformError {}
+
+/// A view of the display at its current resolution
+#[derive(Debug, Clone, Copy)]
+pub struct Framebuffer<'a> {
+    /// Width of the display in pixels
+    pub width: usize,
+    /// Height of the display in pixels
+    pub height: usize,
+    /// Pixels in row-major order, `width * height` entries long.
+    ///
+    /// Each pixel is a 2-bit colour index: bit 0 is set by the first drawing
+    /// plane and bit 1 by the second, which only XO-CHIP programs use.
+    pub pixels: &'a [u8],
+}
+
 pub struct Chip8 {
     program_counter: u16,
-    memory: [u8; MEMORY_SIZE],
-    framebuffer: [bool; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+    memory: Vec<u8>,
+    framebuffer: [u8; HIRES_DISPLAY_WIDTH * HIRES_DISPLAY_HEIGHT],
     general_registers: [u8; REGISTER_COUNT],
     index_register: u16,
     stack_pointer: u16,
@@ -39,15 +259,38 @@ pub struct Chip8 {
     input_keys: [bool; KEY_COUNT],
     delay_timer: u8,
     sound_timer: u8,
+    quirks: Quirks,
+    platform: Platform,
+    vblank_ready: bool,
+    hires: bool,
+    halted: bool,
+    selected_planes: u8,
+    rpl_flags: [u8; RPL_FLAG_COUNT],
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pitch: u8,
+    audio_phase: f64,
+    flag_storage: Option<Box<dyn FlagStorage>>,
+    rng: Box<dyn RandomSource>,
+    rom_hash: u64,
 }
 
 impl Chip8 {
     /// Creates a new Chip-8 emulator instance
     pub fn initialize() -> Self {
+        Self::with_quirks(Quirks::default())
+    }
+
+    /// Creates a new Chip-8 emulator instance with the given quirks
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        Self::new(Platform::Chip8, quirks)
+    }
+
+    /// Creates a new emulator instance for the given platform and quirks
+    pub fn new(platform: Platform, quirks: Quirks) -> Self {
         let mut instance = Self {
             program_counter: STARTING_ADDRESS,
-            memory: [0; MEMORY_SIZE],
-            framebuffer: [false; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            memory: vec![0; platform.memory_size()],
+            framebuffer: [0; HIRES_DISPLAY_WIDTH * HIRES_DISPLAY_HEIGHT],
             general_registers: [0; REGISTER_COUNT],
             index_register: 0,
             stack_pointer: 0,
@@ -55,17 +298,30 @@ impl Chip8 {
             input_keys: [false; KEY_COUNT],
             delay_timer: 0,
             sound_timer: 0,
+            quirks,
+            platform,
+            vblank_ready: true,
+            hires: false,
+            halted: false,
+            selected_planes: 1,
+            rpl_flags: [0; RPL_FLAG_COUNT],
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
+            audio_phase: 0.0,
+            flag_storage: None,
+            rng: Box::new(XorShiftRng::default()),
+            rom_hash: FNV_OFFSET_BASIS,
         };
 
-        instance.memory[..FONT_DATA_SIZE].copy_from_slice(&FONT_DATA);
+        instance.load_fonts();
         instance
     }
 
     /// Resets the emulator state
     pub fn reset(&mut self) {
         self.program_counter = STARTING_ADDRESS;
-        self.memory = [0; MEMORY_SIZE];
-        self.framebuffer = [false; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        self.memory.fill(0);
+        self.framebuffer = [0; HIRES_DISPLAY_WIDTH * HIRES_DISPLAY_HEIGHT];
         self.general_registers = [0; REGISTER_COUNT];
         self.index_register = 0;
         self.stack_pointer = 0;
@@ -73,31 +329,283 @@ impl Chip8 {
         self.input_keys = [false; KEY_COUNT];
         self.delay_timer = 0;
         self.sound_timer = 0;
+        self.vblank_ready = true;
+        self.hires = false;
+        self.halted = false;
+        self.selected_planes = 1;
+        self.rpl_flags = [0; RPL_FLAG_COUNT];
+        self.audio_pattern = [0; AUDIO_PATTERN_SIZE];
+        self.pitch = DEFAULT_PITCH;
+        self.audio_phase = 0.0;
+        self.rom_hash = FNV_OFFSET_BASIS;
+
+        self.load_fonts();
+    }
 
+    /// Copies the small and big hex fonts into interpreter memory
+    fn load_fonts(&mut self) {
         self.memory[..FONT_DATA_SIZE].copy_from_slice(&FONT_DATA);
+        self.memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + BIG_FONT_DATA_SIZE].copy_from_slice(&BIG_FONT_DATA);
+    }
+
+    /// Sets where FX75 persists the RPL user flags between runs
+    pub fn set_flag_storage(&mut self, storage: Box<dyn FlagStorage>) {
+        self.flag_storage = Some(storage);
+    }
+
+    /// Replaces the random source used by CXNN
+    pub fn set_random_source(&mut self, rng: Box<dyn RandomSource>) {
+        self.rng = rng;
+    }
+
+    /// Reseeds CXNN with the default generator and the given seed
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Box::new(XorShiftRng::new(seed));
+    }
+
+    /// Returns a hash identifying the program loaded since the last reset
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
     }
 
     /// Pushes a value onto the stack
-    fn push_to_stack(&mut self, value: u16) {
-        self.call_stack[self.stack_pointer as usize] = value;
+    fn push_to_stack(&mut self, value: u16) -> Result<(), Chip8Error> {
+        let slot = self
+            .call_stack
+            .get_mut(self.stack_pointer as usize)
+            .ok_or(Chip8Error::StackOverflow)?;
+        *slot = value;
         self.stack_pointer += 1;
+        Ok(())
     }
 
     /// Pops a value from the stack
-    fn pop_from_stack(&mut self) -> u16 {
+    fn pop_from_stack(&mut self) -> Result<u16, Chip8Error> {
+        if self.stack_pointer == 0 {
+            return Err(Chip8Error::StackUnderflow);
+        }
         self.stack_pointer -= 1;
-        self.call_stack[self.stack_pointer as usize]
+        Ok(self.call_stack[self.stack_pointer as usize])
+    }
+
+    /// Reads a byte of memory, failing if the address is out of range
+    fn read_byte(&self, addr: usize) -> Result<u8, Chip8Error> {
+        self.memory
+            .get(addr)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds { addr })
+    }
+
+    /// Writes a byte of memory, failing if the address is out of range
+    fn write_byte(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let byte = self
+            .memory
+            .get_mut(addr)
+            .ok_or(Chip8Error::MemoryOutOfBounds { addr })?;
+        *byte = value;
+        Ok(())
     }
 
     /// Executes one CPU cycle
-    pub fn cycle(&mut self) {
-        let opcode = self.fetch_opcode();
-        self.execute_opcode(opcode);
+    pub fn cycle(&mut self) -> Result<(), Chip8Error> {
+        if self.halted {
+            return Ok(());
+        }
+        let opcode = self.fetch_opcode()?;
+        self.execute_opcode(opcode)
+    }
+
+    /// Returns the quirks this instance was built with
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Returns the platform this instance emulates
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Returns true once the program has exited through 00FD
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Returns the address of the next instruction
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    /// Moves execution to another address
+    pub fn set_program_counter(&mut self, addr: u16) {
+        self.program_counter = addr;
+    }
+
+    /// Returns the value of register VX
+    pub fn register(&self, x: usize) -> u8 {
+        self.general_registers[x & 0xF]
+    }
+
+    /// Sets the value of register VX
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.general_registers[x & 0xF] = value;
+    }
+
+    /// Returns the index register I
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    /// Sets the index register I
+    pub fn set_index_register(&mut self, value: u16) {
+        self.index_register = value;
+    }
+
+    /// Returns the number of return addresses on the call stack
+    pub fn stack_pointer(&self) -> u16 {
+        self.stack_pointer
+    }
+
+    /// Returns the return addresses on the call stack, oldest first
+    pub fn call_stack(&self) -> &[u16] {
+        &self.call_stack[..self.stack_pointer as usize]
+    }
+
+    /// Returns the current value of the delay timer
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// Sets the delay timer
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    /// Sets the sound timer
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// Returns the whole of interpreter memory
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Writes bytes into memory, failing without writing anything if they do not fit
+    pub fn write_memory(&mut self, addr: usize, data: &[u8]) -> Result<(), Chip8Error> {
+        let Some(end) = addr.checked_add(data.len()).filter(|&end| end <= self.memory.len()) else {
+            return Err(Chip8Error::MemoryOutOfBounds { addr: addr.max(self.memory.len()) });
+        };
+        self.memory[addr..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Retrieves the display buffer at the current resolution
+    pub fn get_framebuffer(&self) -> Framebuffer<'_> {
+        let width = self.display_width();
+        let height = self.display_height();
+        Framebuffer {
+            width,
+            height,
+            pixels: &self.framebuffer[..width * height],
+        }
+    }
+
+    /// Width of the display at the current resolution
+    fn display_width(&self) -> usize {
+        if self.hires { HIRES_DISPLAY_WIDTH } else { DISPLAY_WIDTH }
+    }
+
+    /// Height of the display at the current resolution
+    fn display_height(&self) -> usize {
+        if self.hires { HIRES_DISPLAY_HEIGHT } else { DISPLAY_HEIGHT }
+    }
+
+    /// Returns true if the SUPER-CHIP instructions are available
+    fn has_schip_instructions(&self) -> bool {
+        self.platform != Platform::Chip8
+    }
+
+    /// Returns true if the XO-CHIP instructions are available
+    fn has_xo_instructions(&self) -> bool {
+        self.platform == Platform::XoChip
     }
 
-    /// Retrieves the display buffer
-    pub fn get_framebuffer(&self) -> &[bool] {
-        &self.framebuffer
+    /// Skips the next instruction, including both words of an XO-CHIP F000 NNNN
+    fn skip_next_instruction(&mut self) {
+        let pc = self.program_counter as usize;
+        let is_long_load = self.memory.get(pc) == Some(&0xF0) && self.memory.get(pc + 1) == Some(&0x00);
+        let length = if is_long_load && self.has_xo_instructions() { 4 } else { 2 };
+        self.program_counter = self.program_counter.wrapping_add(length);
+    }
+
+    /// Switches between low and high resolution, clearing every plane of the display
+    fn set_resolution(&mut self, hires: bool) {
+        self.hires = hires;
+        self.framebuffer = [0; HIRES_DISPLAY_WIDTH * HIRES_DISPLAY_HEIGHT];
+    }
+
+    /// Clears the selected planes of the display
+    fn clear_selected_planes(&mut self) {
+        let keep = !self.selected_planes;
+        for pixel in self.framebuffer.iter_mut() {
+            *pixel &= keep;
+        }
+    }
+
+    /// Scrolls the selected planes by the given offsets, filling uncovered pixels with black
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let width = self.display_width() as isize;
+        let height = self.display_height() as isize;
+        let planes = self.selected_planes;
+        let previous = self.framebuffer;
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = (x - dx, y - dy);
+                let inside = (0..width).contains(&src_x) && (0..height).contains(&src_y);
+                let moved = if inside { previous[(src_x + src_y * width) as usize] & planes } else { 0 };
+                let pixel = &mut self.framebuffer[(x + y * width) as usize];
+                *pixel = (*pixel & !planes) | moved;
+            }
+        }
+    }
+
+    /// Draws a sprite from memory at I into every selected plane, returning true
+    /// if any lit pixel was erased. Each selected plane reads its own copy of the
+    /// sprite data, one after the other.
+    fn draw_sprite(&mut self, x: usize, y: usize, row_bytes: usize, rows: usize) -> Result<bool, Chip8Error> {
+        let width = self.display_width();
+        let height = self.display_height();
+        let x = x % width;
+        let y = y % height;
+
+        let mut flipped = false;
+        let mut address = self.index_register as usize;
+        for plane in [1u8, 2] {
+            if self.selected_planes & plane == 0 {
+                continue;
+            }
+            for row in 0..rows {
+                if self.quirks.clip_sprites && y + row >= height {
+                    break;
+                }
+                for byte in 0..row_bytes {
+                    let sprite_byte = self.read_byte(address + row * row_bytes + byte)?;
+                    for bit in 0..8 {
+                        let col = byte * 8 + bit;
+                        if self.quirks.clip_sprites && x + col >= width {
+                            break;
+                        }
+                        if (sprite_byte & (0x80 >> bit)) != 0 {
+                            let idx = (x + col) % width + ((y + row) % height) * width;
+                            flipped |= self.framebuffer[idx] & plane != 0;
+                            self.framebuffer[idx] ^= plane;
+                        }
+                    }
+                }
+            }
+            address += rows * row_bytes;
+        }
+        Ok(flipped)
     }
 
     /// Sets the state of a specific key
@@ -106,214 +614,312 @@ impl Chip8 {
     }
 
     /// Loads a program into memory starting at the program start address
-    pub fn load_program(&mut self, program_data: &[u8]) {
-        let start_address = STARTING_ADDRESS as usize;
-        let end_address = start_address + program_data.len();
-        self.memory[start_address..end_address].copy_from_slice(program_data);
+    pub fn load_program(&mut self, program_data: &[u8]) -> Result<LoadInfo, LoadError> {
+        self.load_program_at(STARTING_ADDRESS, program_data)
+    }
+
+    /// Loads a program into memory starting at the given address
+    pub fn load_program_at(&mut self, address: u16, program_data: &[u8]) -> Result<LoadInfo, LoadError> {
+        let info = self.check_segment(address, program_data)?;
+        self.memory[info.start_address..info.end_address].copy_from_slice(program_data);
+        self.rom_hash = fnv1a(self.rom_hash, program_data);
+        Ok(info)
+    }
+
+    /// Loads several segments at once, leaving memory untouched unless every segment fits.
+    ///
+    /// Empty segments write nothing, so they never overlap another segment.
+    pub fn load_segments(&mut self, segments: &[(u16, &[u8])]) -> Result<Vec<LoadInfo>, LoadError> {
+        let mut infos = Vec::with_capacity(segments.len());
+        for &(address, data) in segments {
+            let info = self.check_segment(address, data)?;
+            if let Some(other) = infos.iter().find(|other: &&LoadInfo| {
+                info.bytes_loaded > 0
+                    && other.bytes_loaded > 0
+                    && info.start_address < other.end_address
+                    && other.start_address < info.end_address
+            }) {
+                return Err(LoadError::Overlap {
+                    address: info.start_address.max(other.start_address),
+                });
+            }
+            infos.push(info);
+        }
+
+        for (info, &(_, data)) in infos.iter().zip(segments) {
+            self.memory[info.start_address..info.end_address].copy_from_slice(data);
+            self.rom_hash = fnv1a(self.rom_hash, data);
+        }
+        Ok(infos)
+    }
+
+    /// Checks that a segment fits into memory at the given address
+    fn check_segment(&self, address: u16, data: &[u8]) -> Result<LoadInfo, LoadError> {
+        let start_address = address as usize;
+        if start_address >= self.memory.len() {
+            return Err(LoadError::AddressOutOfRange { address: start_address });
+        }
+
+        let available = self.memory.len() - start_address;
+        if data.len() > available {
+            return Err(LoadError::TooLarge { size: data.len(), available });
+        }
+
+        Ok(LoadInfo {
+            start_address,
+            end_address: start_address + data.len(),
+            bytes_loaded: data.len(),
+            bytes_remaining: available - data.len(),
+        })
     }
 
     /// Fetches the next opcode from memory
-    fn fetch_opcode(&mut self) -> u16 {
-        let high_byte = self.memory[self.program_counter as usize] as u16;
-        let low_byte = self.memory[(self.program_counter + 1) as usize] as u16;
-        self.program_counter += 2;
-        (high_byte << 8) | low_byte
+    fn fetch_opcode(&mut self) -> Result<u16, Chip8Error> {
+        let address = self.program_counter as usize;
+        let high_byte = self.read_byte(address)? as u16;
+        let low_byte = self.read_byte(address + 1)? as u16;
+        self.program_counter = self.program_counter.wrapping_add(2);
+        Ok((high_byte << 8) | low_byte)
     }
 
     /// Decodes and executes a given opcode
-    fn execute_opcode(&mut self, opcode: u16) {
-        let nibble1 = (opcode & 0xF000) >> 12;
-        let nibble2 = (opcode & 0x0F00) >> 8;
-        let nibble3 = (opcode & 0x00F0) >> 4;
-        let nibble4 = opcode & 0x000F;
-
-        match (nibble1, nibble2, nibble3, nibble4) {
-            (1, _, _, _) => {
+    fn execute_opcode(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let invalid = Chip8Error::InvalidOpcode {
+            pc: self.program_counter.wrapping_sub(2),
+            opcode,
+        };
+        let instruction = Instruction::decode(opcode).map_err(|_| invalid)?;
+        if !self.platform.supports(instruction.platform()) {
+            return Err(invalid);
+        }
+        self.execute_instruction(instruction)
+    }
+
+    /// Executes a decoded instruction
+    fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        match instruction {
+            Instruction::Nop => {},
+            Instruction::ClearScreen => self.clear_selected_planes(),
+            Instruction::Return => {
+                let return_address = self.pop_from_stack()?;
+                self.program_counter = return_address;
+            },
+            // Scroll down N rows - 00CN
+            Instruction::ScrollDown { n } => self.scroll(0, n as isize),
+            // Scroll up N rows - 00DN
+            Instruction::ScrollUp { n } => self.scroll(0, -(n as isize)),
+            // Scroll right 4 pixels - 00FB
+            Instruction::ScrollRight => self.scroll(4, 0),
+            // Scroll left 4 pixels - 00FC
+            Instruction::ScrollLeft => self.scroll(-4, 0),
+            // Exit the interpreter - 00FD
+            Instruction::Exit => self.halted = true,
+            // Low resolution - 00FE
+            Instruction::LowRes => self.set_resolution(false),
+            // High resolution - 00FF
+            Instruction::HighRes => self.set_resolution(true),
+            Instruction::Jump { addr } => {
                 // Jump to address NNN
-                let address = opcode & 0x0FFF;
-                self.program_counter = address;
+                self.program_counter = addr;
             },
-            (2, _, _, _) => {
+            Instruction::Call { addr } => {
                 // Call subroutine at NNN
-                let address = opcode & 0x0FFF;
-                self.push_to_stack(self.program_counter);
-                self.program_counter = address;
+                self.push_to_stack(self.program_counter)?;
+                self.program_counter = addr;
             },
-            (3, _, _, _) => {
+            Instruction::SkipIfEqual { x, byte } => {
                 // Skip next instruction if VX == NN
-                let x = nibble2 as usize;
-                let value = (opcode & 0x00FF) as u8;
-                if self.general_registers[x] == value {
-                    self.program_counter += 2;
+                if self.general_registers[x as usize] == byte {
+                    self.skip_next_instruction();
                 }
             },
-            (4, _, _, _) => {
+            Instruction::SkipIfNotEqual { x, byte } => {
                 // Skip next instruction if VX != NN
-                let x = nibble2 as usize;
-                let value = (opcode & 0x00FF) as u8;
-                if self.general_registers[x] != value {
-                    self.program_counter += 2;
+                if self.general_registers[x as usize] != byte {
+                    self.skip_next_instruction();
                 }
             },
-            (5, _, _, 0) => {
+            Instruction::SkipIfRegistersEqual { x, y } => {
                 // Skip next instruction if VX == VY
-                let x = nibble2 as usize;
-                let y = nibble3 as usize;
-                if self.general_registers[x] == self.general_registers[y] {
-                    self.program_counter += 2;
+                if self.general_registers[x as usize] == self.general_registers[y as usize] {
+                    self.skip_next_instruction();
+                }
+            },
+            // Save VX - VY to memory at I - 5XY2
+            Instruction::SaveRange { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let i = self.index_register as usize;
+                for offset in 0..=x.abs_diff(y) {
+                    let register = Self::register_towards(x, y, offset);
+                    self.write_byte(i + offset, self.general_registers[register])?;
                 }
             },
-            (6, _, _, _) => {
+            // Load VX - VY from memory at I - 5XY3
+            Instruction::LoadRange { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let i = self.index_register as usize;
+                for offset in 0..=x.abs_diff(y) {
+                    let register = Self::register_towards(x, y, offset);
+                    self.general_registers[register] = self.read_byte(i + offset)?;
+                }
+            },
+            Instruction::LoadByte { x, byte } => {
                 // Set VX to NN
-                let x = nibble2 as usize;
-                let value = (opcode & 0x00FF) as u8;
-                self.general_registers[x] = value;
+                self.general_registers[x as usize] = byte;
             },
-            (7, _, _, _) => {
+            Instruction::AddByte { x, byte } => {
                 // Add NN to VX
-                let x = nibble2 as usize;
-                let value = (opcode & 0x00FF) as u8;
-                self.general_registers[x] = self.general_registers[x].wrapping_add(value);
+                let x = x as usize;
+                self.general_registers[x] = self.general_registers[x].wrapping_add(byte);
             },
-            (8, _, _, 0) => {
+            Instruction::Move { x, y } => {
                 // Set VX to VY
-                let x = nibble2 as usize;
-                let y = nibble3 as usize;
-                self.general_registers[x] = self.general_registers[y];
-            },
-            (0,0,0xE,0xE) => {let retaddr=self.pop_from_stack(); self.program_counter=retaddr;},//return to addr
-            (0,0,0xE,0) => {self.framebuffer =[false;DISPLAY_WIDTH*DISPLAY_HEIGHT];}, //clear screen
-            (0,0,0,0) => return,//nop
-            (8, _, _, 1) => {
+                self.general_registers[x as usize] = self.general_registers[y as usize];
+            },
+            Instruction::Or { x, y } => {
                 // Set VX to VX OR VY
-                let x = nibble2 as usize;
-                let y = nibble3 as usize;
-                self.general_registers[x] |= self.general_registers[y];
+                self.general_registers[x as usize] |= self.general_registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.general_registers[0xF] = 0;
+                }
             },
-            (8, _, _, 2) => {
+            Instruction::And { x, y } => {
                 // Set VX to VX AND VY
-                let x = nibble2 as usize;
-                let y = nibble3 as usize;
-                self.general_registers[x] &= self.general_registers[y];
+                self.general_registers[x as usize] &= self.general_registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.general_registers[0xF] = 0;
+                }
             },
-            (8, _, _, 3) => {
+            Instruction::Xor { x, y } => {
                 // Set VX to VX XOR VY
-                let x = nibble2 as usize;
-                let y = nibble3 as usize;
-                self.general_registers[x] ^= self.general_registers[y];
+                self.general_registers[x as usize] ^= self.general_registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.general_registers[0xF] = 0;
+                }
             },
-            (8, _, _, 4) => {
+            Instruction::Add { x, y } => {
                 // Add VY to VX, set VF to carry
-                let x = nibble2 as usize;
-                let y = nibble3 as usize;
+                let (x, y) = (x as usize, y as usize);
                 let (sum, carry) = self.general_registers[x].overflowing_add(self.general_registers[y]);
                 self.general_registers[x] = sum;
                 self.general_registers[0xF] = if carry { 1 } else { 0 };
             },
-            (8, _, _, 5) => {
+            Instruction::Sub { x, y } => {
                 // Subtract VY from VX, set VF to NOT borrow
-                let x = nibble2 as usize;
-                let y = nibble3 as usize;
+                let (x, y) = (x as usize, y as usize);
                 let (diff, borrow) = self.general_registers[x].overflowing_sub(self.general_registers[y]);
                 self.general_registers[x] = diff;
                 self.general_registers[0xF] = if borrow { 0 } else { 1 };
             },
-            (8, _, _, 6) => {
+            Instruction::ShiftRight { x, y } => {
                 // Shift VX right by 1, set VF to LSB
-                let x = nibble2 as usize;
-                self.general_registers[0xF] = self.general_registers[x] & 0x1;
-                self.general_registers[x] >>= 1;
+                let value = self.shift_source(x as usize, y as usize);
+                self.general_registers[x as usize] = value >> 1;
+                self.general_registers[0xF] = value & 0x1;
             },
-            (8, _, _, 7) => {
+            Instruction::SubReverse { x, y } => {
                 // Set VX to VY - VX, set VF to NOT borrow
-                let x = nibble2 as usize;
-                let y = nibble3 as usize;
+                let (x, y) = (x as usize, y as usize);
                 let (diff, borrow) = self.general_registers[y].overflowing_sub(self.general_registers[x]);
                 self.general_registers[x] = diff;
                 self.general_registers[0xF] = if borrow { 0 } else { 1 };
             },
-            (8, _, _, 0xE) => {
+            Instruction::ShiftLeft { x, y } => {
                 // Shift VX left by 1, set VF to MSB
-                let x = nibble2 as usize;
-                self.general_registers[0xF] = (self.general_registers[x] & 0x80) >> 7;
-                self.general_registers[x] <<= 1;
+                let value = self.shift_source(x as usize, y as usize);
+                self.general_registers[x as usize] = value << 1;
+                self.general_registers[0xF] = (value & 0x80) >> 7;
             },
-            (9, _, _, 0) => {
+            Instruction::SkipIfRegistersNotEqual { x, y } => {
                 // Skip next instruction if VX != VY
-                let x = nibble2 as usize;
-                let y = nibble3 as usize;
-                if self.general_registers[x] != self.general_registers[y] {
-                    self.program_counter += 2;
+                if self.general_registers[x as usize] != self.general_registers[y as usize] {
+                    self.skip_next_instruction();
                 }
             },
-            (0xA, _, _, _) => {
+            Instruction::LoadIndex { addr } => {
                 // Set I to NNN
-                self.index_register = opcode & 0x0FFF;
+                self.index_register = addr;
             },
             // JMP V0 + NNN
-            (0xB, _, _, _) => {
-                let nnn = opcode & 0xFFF;
-                self.program_counter = (self.general_registers[0] as u16) + nnn;
+            Instruction::JumpOffset { addr } => {
+                let offset = if self.quirks.jump_uses_vx { (addr >> 8) as usize } else { 0 };
+                self.program_counter = (self.general_registers[offset] as u16) + addr;
             },
             // VX = rand() & NN
-            (0xC, _, _, _) => {
-                let x = nibble2 as usize;
-                let nn = (opcode & 0xFF) as u8;
-                let rng: u8 = random();
-                self.general_registers[x] = rng & nn;
+            Instruction::Random { x, byte } => {
+                let rng = self.rng.next_byte();
+                self.general_registers[x as usize] = rng & byte;
             },
-            (0xD, _, _, _) => {
+            Instruction::Draw { x, y, n } => {
                 // Display/draw a sprite
-                let x = self.general_registers[nibble2 as usize] as usize % DISPLAY_WIDTH;
-                let y = self.general_registers[nibble3 as usize] as usize % DISPLAY_HEIGHT;
-                let height = nibble4;
-
-                let mut flipped = false;
-                for row in 0..height {
-                    let sprite_byte = self.memory[(self.index_register + row as u16) as usize];
-                    for col in 0..8 {
-                        if (sprite_byte & (0x80 >> col)) != 0 {
-                            let idx = (x + col) % DISPLAY_WIDTH + ((y + row as usize) % DISPLAY_HEIGHT) * DISPLAY_WIDTH;
-                            flipped |= self.framebuffer[idx];
-                            self.framebuffer[idx] ^= true;
-                        }
+                if self.quirks.display_wait {
+                    if !self.vblank_ready {
+                        // Redo opcode once the next frame starts
+                        self.program_counter = self.program_counter.wrapping_sub(2);
+                        return Ok(());
                     }
+                    self.vblank_ready = false;
                 }
+
+                let x = self.general_registers[x as usize] as usize;
+                let y = self.general_registers[y as usize] as usize;
+
+                // DXY0 draws a 16x16 sprite on SUPER-CHIP
+                let flipped = if n == 0 && self.has_schip_instructions() {
+                    self.draw_sprite(x, y, 2, 16)?
+                } else {
+                    self.draw_sprite(x, y, 1, n as usize)?
+                };
                 self.general_registers[0xF] = if flipped { 1 } else { 0 };
             },
             // Skip if key pressed - EX9E
-            (0xE, _, 9, 0xE) => {
-                let x = nibble2 as usize;
-                let vx = self.general_registers[x];
-                let key = self.input_keys[vx as usize];
+            Instruction::SkipIfKeyPressed { x } => {
+                let vx = self.general_registers[x as usize];
+                let key = self.input_keys[(vx & 0xF) as usize];
                 if key {
-                    self.program_counter += 2;
+                    self.skip_next_instruction();
                 }
             },
 
             // Skip if key not pressed - EXA1
-            (0xE, _, 0xA, 1) => {
-                let x = nibble2 as usize;
-                let vx = self.general_registers[x];
-                let key = self.input_keys[vx as usize];
+            Instruction::SkipIfKeyNotPressed { x } => {
+                let vx = self.general_registers[x as usize];
+                let key = self.input_keys[(vx & 0xF) as usize];
                 if !key {
-                    self.program_counter += 2;
+                    self.skip_next_instruction();
                 }
             },
 
+            // I = NNNN - F000 NNNN
+            Instruction::LoadLongIndex => {
+                let address = self.program_counter as usize;
+                let high_byte = self.read_byte(address)? as u16;
+                let low_byte = self.read_byte(address + 1)? as u16;
+                self.index_register = (high_byte << 8) | low_byte;
+                self.program_counter = self.program_counter.wrapping_add(2);
+            },
+            // Select drawing planes - FN01
+            Instruction::SelectPlanes { planes } => {
+                self.selected_planes = planes;
+            },
+            // Load audio pattern from memory at I - F002
+            Instruction::LoadAudioPattern => {
+                let i = self.index_register as usize;
+                for offset in 0..AUDIO_PATTERN_SIZE {
+                    self.audio_pattern[offset] = self.read_byte(i + offset)?;
+                }
+            },
             // VX = DT - FX07
-            (0xF, _, 0, 7) => {
-                let x = nibble2 as usize;
-                self.general_registers[x] = self.delay_timer;
+            Instruction::LoadDelayTimer { x } => {
+                self.general_registers[x as usize] = self.delay_timer;
             },
 
             // Wait Key Press - FX0A
-            (0xF, _, 0, 0xA) => {
-                let x = nibble2 as usize;
+            Instruction::WaitForKey { x } => {
                 let mut pressed = false;
                 for i in 0..self.input_keys.len() {
                     if self.input_keys[i] {
-                        self.general_registers[x] = i as u8;
+                        self.general_registers[x as usize] = i as u8;
                         pressed = true;
                         break;
                     }
@@ -321,35 +927,39 @@ impl Chip8 {
 
                 if !pressed {
                     // Redo opcode
-                    self.program_counter -= 2;
+                    self.program_counter = self.program_counter.wrapping_sub(2);
                 }
             },
             // DT = VX - FX15
-            (0xF, _, 1, 5) => {
-                let x = nibble2 as usize;
-                self.delay_timer = self.general_registers[x];
+            Instruction::SetDelayTimer { x } => {
+                self.delay_timer = self.general_registers[x as usize];
             },
             // ST = VX - FX18
-            (0xF, _, 1, 8) => {
-                let x = nibble2 as usize;
-                self.sound_timer = self.general_registers[x];
+            Instruction::SetSoundTimer { x } => {
+                self.sound_timer = self.general_registers[x as usize];
             },
             // I += VX - FX1E
-            (0xF, _, 1, 0xE) => {
-                let x = nibble2 as usize;
-                let vx = self.general_registers[x] as u16;
+            Instruction::AddIndex { x } => {
+                let vx = self.general_registers[x as usize] as u16;
                 self.index_register = self.index_register.wrapping_add(vx);
             },
             // I = FONT - FX29
-            (0xF, _, 2, 9) => {
-                let x = nibble2 as usize;
-                let c = self.general_registers[x] as u16;
+            Instruction::LoadFont { x } => {
+                let c = self.general_registers[x as usize] as u16;
                 self.index_register = c * 5;
             },
+            // I = BIG FONT - FX30
+            Instruction::LoadBigFont { x } => {
+                let c = (self.general_registers[x as usize] & 0xF) as u16;
+                self.index_register = BIG_FONT_ADDRESS as u16 + c * 10;
+            },
+            // PITCH = VX - FX3A
+            Instruction::SetPitch { x } => {
+                self.pitch = self.general_registers[x as usize];
+            },
             // BCD of VX - FX33
-            (0xF, _, 3, 3) => {
-                let x = nibble2 as usize;
-                let vx = self.general_registers[x] as f32;
+            Instruction::StoreBcd { x } => {
+                let vx = self.general_registers[x as usize] as f32;
 
                 // Fetch the hundreds digit by dividing by 100 and tossing the decimal
                 let hundreds = (vx / 100.0).floor() as u8;
@@ -358,33 +968,70 @@ impl Chip8 {
                 // Fetch the ones digit by tossing the hundreds and the tens
                 let ones = (vx % 10.0) as u8;
 
-                self.memory[self.index_register as usize] = hundreds;
-                self.memory[(self.index_register + 1) as usize] = tens;
-                self.memory[(self.index_register + 2) as usize] = ones;
+                let i = self.index_register as usize;
+                self.write_byte(i, hundreds)?;
+                self.write_byte(i + 1, tens)?;
+                self.write_byte(i + 2, ones)?;
             },
             // STORE V0 - VX - FX55
-            (0xF, _, 5, 5) => {
-                let x = nibble2 as usize;
+            Instruction::StoreRegisters { x } => {
+                let x = x as usize;
                 let i = self.index_register as usize;
                 for idx in 0..=x {
-                    self.memory[i + idx] = self.general_registers[idx];
+                    self.write_byte(i + idx, self.general_registers[idx])?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.index_register = self.index_register.wrapping_add(x as u16 + 1);
                 }
             },
             // LOAD V0 - VX - FX65
-            (0xF, _, 6, 5) => {
-                let x = nibble2 as usize;
+            Instruction::LoadRegisters { x } => {
+                let x = x as usize;
                 let i = self.index_register as usize;
                 for idx in 0..=x {
-                    self.general_registers[idx] = self.memory[i + idx];
+                    self.general_registers[idx] = self.read_byte(i + idx)?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.index_register = self.index_register.wrapping_add(x as u16 + 1);
                 }
             },
-            (_,_,_,_) => unimplemented!("Not a valid opcode: {}",opcode),        }
+            // SAVE V0 - VX TO FLAGS - FX75
+            Instruction::StoreFlags { x } => {
+                let x = x as usize;
+                self.rpl_flags[..=x].copy_from_slice(&self.general_registers[..=x]);
+                if let Some(storage) = self.flag_storage.as_mut() {
+                    storage.save_flags(self.rom_hash, &self.rpl_flags);
+                }
+            },
+            // LOAD V0 - VX FROM FLAGS - FX85
+            Instruction::LoadFlags { x } => {
+                let x = x as usize;
+                if let Some(saved) = self.flag_storage.as_mut().and_then(|storage| storage.load_flags(self.rom_hash)) {
+                    self.rpl_flags = saved;
+                }
+                self.general_registers[..=x].copy_from_slice(&self.rpl_flags[..=x]);
+            },
+        }
+        Ok(())
     }
 
+    /// Returns the register `offset` steps from X towards Y, for 5XY2/5XY3
+    fn register_towards(x: usize, y: usize, offset: usize) -> usize {
+        if x <= y { x + offset } else { x - offset }
+    }
 
+    /// Picks the register a shift instruction reads from
+    fn shift_source(&self, x: usize, y: usize) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.general_registers[y]
+        } else {
+            self.general_registers[x]
+        }
+    }
 
     /// Updates the delay and sound timers
     pub fn update_timers(&mut self) {
+        self.vblank_ready = true;
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
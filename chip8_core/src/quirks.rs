@@ -0,0 +1,59 @@
+/// Selects how the core interprets instructions whose behaviour differs
+/// between CHIP-8 implementations.
+///
+/// The default matches the interpretation this core has always used, which
+/// is what most modern ROMs expect. [`Quirks::cosmac_vip`] matches the
+/// original COSMAC VIP interpreter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VY and store the result in VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    /// FX55/FX65 leave I pointing past the last register stored or loaded
+    pub load_store_increments_i: bool,
+    /// BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump_uses_vx: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to zero
+    pub logic_resets_vf: bool,
+    /// DXYN clips sprites at the screen edges instead of wrapping them around
+    pub clip_sprites: bool,
+    /// DXYN waits for the next frame before drawing, limiting draws to one per frame
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// Quirks of the original COSMAC VIP interpreter
+    pub const fn cosmac_vip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            clip_sprites: true,
+            display_wait: true,
+        }
+    }
+
+    /// Quirks of the SUPER-CHIP 1.1 interpreter on the HP48
+    pub const fn super_chip() -> Self {
+        Self {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    /// Quirks of the XO-CHIP reference interpreter, Octo
+    pub const fn xo_chip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use crate::Chip8;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Frames the core expects per second of emulated time
+const FRAMES_PER_SECOND: u64 = 60;
+
+/// Delta tag: the payload is an XOR run-length encoding against the newer state
+const DELTA_XOR: u8 = 0;
+/// Delta tag: the payload is the complete older state
+const DELTA_FULL: u8 = 1;
+
+/// Keeps a history of recent machine states so play can be stepped backwards.
+///
+/// The newest snapshot is kept whole. Every older snapshot is stored as a
+/// delta that turns the next newer state back into it, so consecutive frames
+/// that barely differ cost only a few bytes each.
+pub struct RewindBuffer {
+    interval: u32,
+    capacity: usize,
+    frames_since_snapshot: u32,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    /// Creates a buffer that takes a snapshot every `interval_frames` frames and
+    /// keeps enough of them to cover `budget` of play
+    pub fn new(interval_frames: u32, budget: Duration) -> Self {
+        let interval = interval_frames.max(1);
+        let frames = budget.as_secs_f64() * FRAMES_PER_SECOND as f64;
+        let capacity = ((frames / interval as f64).ceil() as usize).max(1);
+        Self {
+            interval,
+            capacity,
+            frames_since_snapshot: 0,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Call once per frame; takes a snapshot whenever the interval has elapsed
+    pub fn record_frame(&mut self, chip8: &Chip8) {
+        self.frames_since_snapshot += 1;
+        if self.latest.is_some() && self.frames_since_snapshot < self.interval {
+            return;
+        }
+        self.frames_since_snapshot = 0;
+
+        let state = chip8.save_state();
+        if let Some(previous) = self.latest.take() {
+            self.deltas.push_back(encode_delta(&state, &previous));
+            if self.deltas.len() >= self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.latest = Some(state);
+    }
+
+    /// Restores the most recent snapshot and drops it, so the next call goes
+    /// further back. The oldest snapshot is kept, and restored again on every
+    /// call once the history runs out. Returns false if nothing was recorded.
+    pub fn rewind(&mut self, chip8: &mut Chip8) -> bool {
+        let Some(latest) = self.latest.as_mut() else {
+            return false;
+        };
+        if chip8.load_state(latest).is_err() {
+            self.clear();
+            return false;
+        }
+
+        if let Some(delta) = self.deltas.pop_back() {
+            apply_delta(latest, &delta);
+        }
+        self.frames_since_snapshot = 0;
+        true
+    }
+
+    /// Drops every recorded snapshot
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.frames_since_snapshot = 0;
+    }
+
+    /// Number of snapshots that can still be restored
+    pub fn len(&self) -> usize {
+        self.latest.iter().count() + self.deltas.len()
+    }
+
+    /// Returns true if there is nothing to rewind to
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// Approximate number of bytes used by the recorded history
+    pub fn memory_usage(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len) + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+}
+
+/// Encodes how to turn `newer` back into `older`.
+///
+/// States of equal length are XORed and the result is stored as alternating
+/// runs of unchanged bytes and literal bytes. Anything else falls back to a
+/// full copy of `older`.
+fn encode_delta(newer: &[u8], older: &[u8]) -> Vec<u8> {
+    if newer.len() != older.len() {
+        let mut delta = Vec::with_capacity(older.len() + 1);
+        delta.push(DELTA_FULL);
+        delta.extend_from_slice(older);
+        return delta;
+    }
+
+    let mut delta = vec![DELTA_XOR];
+    let mut position = 0;
+    while position < newer.len() {
+        let unchanged = newer[position..]
+            .iter()
+            .zip(&older[position..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        position += unchanged;
+        let changed = newer[position..]
+            .iter()
+            .zip(&older[position..])
+            .take_while(|(a, b)| a != b)
+            .count();
+
+        write_varint(&mut delta, unchanged);
+        write_varint(&mut delta, changed);
+        delta.extend(
+            newer[position..position + changed]
+                .iter()
+                .zip(&older[position..position + changed])
+                .map(|(a, b)| a ^ b),
+        );
+        position += changed;
+    }
+    delta
+}
+
+/// Turns `state` back into the older state a delta was encoded against
+fn apply_delta(state: &mut Vec<u8>, delta: &[u8]) {
+    let (&tag, mut data) = delta.split_first().expect("deltas always carry a tag");
+    if tag == DELTA_FULL {
+        *state = data.to_vec();
+        return;
+    }
+
+    let mut position = 0;
+    while !data.is_empty() {
+        position += read_varint(&mut data);
+        let changed = read_varint(&mut data);
+        for (byte, xor) in state[position..position + changed].iter_mut().zip(&data[..changed]) {
+            *byte ^= xor;
+        }
+        data = &data[changed..];
+        position += changed;
+    }
+}
+
+/// Appends a LEB128 encoded length
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a LEB128 encoded length and advances past it
+fn read_varint(data: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some((&byte, rest)) = data.split_first() {
+        *data = rest;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
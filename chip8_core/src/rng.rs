@@ -0,0 +1,56 @@
+/// Seed used when no other random source has been chosen
+pub const DEFAULT_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+/// Source of the random bytes used by CXNN.
+///
+/// The generator state is exposed as a single 64-bit value so a run can be
+/// captured and replayed exactly.
+pub trait RandomSource {
+    /// Returns the next random byte
+    fn next_byte(&mut self) -> u8;
+
+    /// Returns the current generator state
+    fn state(&self) -> u64;
+
+    /// Restores a state previously returned by [`RandomSource::state`]
+    fn set_state(&mut self, state: u64);
+}
+
+/// A small, fast xorshift64* generator
+#[derive(Debug, Clone)]
+pub struct XorShiftRng {
+    state: u64,
+}
+
+impl XorShiftRng {
+    /// Creates a generator from a seed. A zero seed is replaced by [`DEFAULT_SEED`],
+    /// since xorshift never leaves the all-zero state.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: DEFAULT_SEED };
+        rng.set_state(seed);
+        rng
+    }
+}
+
+impl Default for XorShiftRng {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+impl RandomSource for XorShiftRng {
+    fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = if state == 0 { DEFAULT_SEED } else { state };
+    }
+}
@@ -0,0 +1,351 @@
+use crate::{
+    crc32, Chip8, Platform, Quirks, AUDIO_PATTERN_SIZE, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH, KEY_COUNT,
+    REGISTER_COUNT, RPL_FLAG_COUNT, STACK_DEPTH,
+};
+use std::fmt;
+
+/// Magic bytes at the start of every save state
+const STATE_MAGIC: [u8; 4] = *b"C8ST";
+/// Version written by [`Chip8::save_state`]
+pub const STATE_VERSION: u16 = 1;
+/// Magic, version, reserved word, payload length and checksum
+const HEADER_SIZE: usize = 4 + 2 + 2 + 4 + 4;
+
+/// Errors raised when a save state cannot be restored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    /// The data does not start with the save state magic bytes
+    BadMagic,
+    /// The state uses a layout version this core cannot read
+    UnsupportedVersion { version: u16 },
+    /// The data ends before the state does
+    Truncated,
+    /// The payload does not match its checksum
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// A field holds a value no machine could be in
+    InvalidField { field: &'static str },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion { version } => write!(f, "unsupported save state version {}", version),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "save state checksum mismatch (expected {:#010X}, found {:#010X})",
+                expected, actual
+            ),
+            StateError::InvalidField { field } => write!(f, "save state has an invalid {}", field),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl Chip8 {
+    /// Captures the complete machine state.
+    ///
+    /// The state starts with a versioned header and a CRC-32 of the payload, so
+    /// [`Chip8::load_state`] can reject damaged or foreign data. The flag storage
+    /// is not part of the state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(self.memory.len() + self.framebuffer.len() + 256);
+        payload.push(platform_to_byte(self.platform));
+        payload.push(quirks_to_byte(self.quirks));
+        payload.extend_from_slice(&self.program_counter.to_le_bytes());
+        payload.extend_from_slice(&self.index_register.to_le_bytes());
+        payload.extend_from_slice(&self.stack_pointer.to_le_bytes());
+        for address in self.call_stack {
+            payload.extend_from_slice(&address.to_le_bytes());
+        }
+        payload.extend_from_slice(&self.general_registers);
+        payload.push(self.delay_timer);
+        payload.push(self.sound_timer);
+        let keys = self
+            .input_keys
+            .iter()
+            .enumerate()
+            .fold(0u16, |keys, (index, &pressed)| keys | ((pressed as u16) << index));
+        payload.extend_from_slice(&keys.to_le_bytes());
+        payload.push(self.vblank_ready as u8);
+        payload.push(self.hires as u8);
+        payload.push(self.halted as u8);
+        payload.push(self.selected_planes);
+        payload.extend_from_slice(&self.rpl_flags);
+        payload.extend_from_slice(&self.audio_pattern);
+        payload.push(self.pitch);
+        payload.extend_from_slice(&self.audio_phase.to_le_bytes());
+        payload.extend_from_slice(&self.rng.state().to_le_bytes());
+        payload.extend_from_slice(&self.rom_hash.to_le_bytes());
+        payload.extend_from_slice(&self.framebuffer);
+        payload.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        payload.extend_from_slice(&self.memory);
+
+        let mut state = Vec::with_capacity(HEADER_SIZE + payload.len());
+        state.extend_from_slice(&STATE_MAGIC);
+        state.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        state.extend_from_slice(&0u16.to_le_bytes());
+        state.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        state.extend_from_slice(&crc32(&payload).to_le_bytes());
+        state.extend_from_slice(&payload);
+        state
+    }
+
+    /// Restores a state captured by [`Chip8::save_state`].
+    ///
+    /// The state is fully validated before anything is changed, so the machine is
+    /// left untouched when an error is returned.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut header = Reader::new(state);
+        if header.bytes(STATE_MAGIC.len())? != STATE_MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let version = header.u16()?;
+        let _reserved = header.u16()?;
+        let length = header.u32()? as usize;
+        let expected = header.u32()?;
+        let payload = header.bytes(length)?;
+        header.finish("state length")?;
+        let actual = crc32(payload);
+        if actual != expected {
+            return Err(StateError::ChecksumMismatch { expected, actual });
+        }
+
+        let snapshot = match version {
+            1 => Snapshot::read_v1(payload)?,
+            _ => return Err(StateError::UnsupportedVersion { version }),
+        };
+        snapshot.apply(self);
+        Ok(())
+    }
+}
+
+/// A fully parsed save state, ready to be applied to a machine
+struct Snapshot {
+    platform: Platform,
+    quirks: Quirks,
+    program_counter: u16,
+    index_register: u16,
+    stack_pointer: u16,
+    call_stack: [u16; STACK_DEPTH],
+    general_registers: [u8; REGISTER_COUNT],
+    delay_timer: u8,
+    sound_timer: u8,
+    input_keys: [bool; KEY_COUNT],
+    vblank_ready: bool,
+    hires: bool,
+    halted: bool,
+    selected_planes: u8,
+    rpl_flags: [u8; RPL_FLAG_COUNT],
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pitch: u8,
+    audio_phase: f64,
+    rng_state: u64,
+    rom_hash: u64,
+    framebuffer: [u8; HIRES_DISPLAY_WIDTH * HIRES_DISPLAY_HEIGHT],
+    memory: Vec<u8>,
+}
+
+impl Snapshot {
+    /// Parses the version 1 payload layout
+    fn read_v1(payload: &[u8]) -> Result<Self, StateError> {
+        let mut reader = Reader::new(payload);
+        let platform = platform_from_byte(reader.u8()?)?;
+        let quirks = quirks_from_byte(reader.u8()?)?;
+        let program_counter = reader.u16()?;
+        let index_register = reader.u16()?;
+        let stack_pointer = reader.u16()?;
+        if stack_pointer as usize > STACK_DEPTH {
+            return Err(StateError::InvalidField { field: "stack pointer" });
+        }
+        let mut call_stack = [0; STACK_DEPTH];
+        for address in call_stack.iter_mut() {
+            *address = reader.u16()?;
+        }
+        let general_registers = reader.array()?;
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let keys = reader.u16()?;
+        let mut input_keys = [false; KEY_COUNT];
+        for (index, pressed) in input_keys.iter_mut().enumerate() {
+            *pressed = keys & (1 << index) != 0;
+        }
+        let vblank_ready = reader.bool()?;
+        let hires = reader.bool()?;
+        let halted = reader.bool()?;
+        let selected_planes = reader.u8()?;
+        if selected_planes > 3 {
+            return Err(StateError::InvalidField { field: "plane selection" });
+        }
+        let rpl_flags = reader.array()?;
+        let audio_pattern = reader.array()?;
+        let pitch = reader.u8()?;
+        let audio_phase = f64::from_le_bytes(reader.array()?);
+        if !(0.0..(AUDIO_PATTERN_SIZE * 8) as f64).contains(&audio_phase) {
+            return Err(StateError::InvalidField { field: "audio phase" });
+        }
+        let rng_state = u64::from_le_bytes(reader.array()?);
+        let rom_hash = u64::from_le_bytes(reader.array()?);
+        let framebuffer = reader.array()?;
+        if framebuffer.iter().any(|&pixel| pixel > 3) {
+            return Err(StateError::InvalidField { field: "framebuffer" });
+        }
+        let memory_size = reader.u32()? as usize;
+        if memory_size != platform.memory_size() {
+            return Err(StateError::InvalidField { field: "memory size" });
+        }
+        let memory = reader.bytes(memory_size)?.to_vec();
+        reader.finish("payload length")?;
+
+        Ok(Self {
+            platform,
+            quirks,
+            program_counter,
+            index_register,
+            stack_pointer,
+            call_stack,
+            general_registers,
+            delay_timer,
+            sound_timer,
+            input_keys,
+            vblank_ready,
+            hires,
+            halted,
+            selected_planes,
+            rpl_flags,
+            audio_pattern,
+            pitch,
+            audio_phase,
+            rng_state,
+            rom_hash,
+            framebuffer,
+            memory,
+        })
+    }
+
+    /// Overwrites the machine state with the snapshot
+    fn apply(self, chip8: &mut Chip8) {
+        chip8.platform = self.platform;
+        chip8.quirks = self.quirks;
+        chip8.program_counter = self.program_counter;
+        chip8.index_register = self.index_register;
+        chip8.stack_pointer = self.stack_pointer;
+        chip8.call_stack = self.call_stack;
+        chip8.general_registers = self.general_registers;
+        chip8.delay_timer = self.delay_timer;
+        chip8.sound_timer = self.sound_timer;
+        chip8.input_keys = self.input_keys;
+        chip8.vblank_ready = self.vblank_ready;
+        chip8.hires = self.hires;
+        chip8.halted = self.halted;
+        chip8.selected_planes = self.selected_planes;
+        chip8.rpl_flags = self.rpl_flags;
+        chip8.audio_pattern = self.audio_pattern;
+        chip8.pitch = self.pitch;
+        chip8.audio_phase = self.audio_phase;
+        chip8.rng.set_state(self.rng_state);
+        chip8.rom_hash = self.rom_hash;
+        chip8.framebuffer = self.framebuffer;
+        chip8.memory = self.memory;
+    }
+}
+
+/// Reads little-endian fields from a byte slice
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < count {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    /// Fails if anything is left after the last field
+    fn finish(&self, field: &'static str) -> Result<(), StateError> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(StateError::InvalidField { field })
+        }
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        Ok(self.bytes(N)?.try_into().expect("slice has the requested length"))
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::InvalidField { field: "flag" }),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
+
+fn platform_to_byte(platform: Platform) -> u8 {
+    match platform {
+        Platform::Chip8 => 0,
+        Platform::SuperChip => 1,
+        Platform::XoChip => 2,
+    }
+}
+
+fn platform_from_byte(byte: u8) -> Result<Platform, StateError> {
+    match byte {
+        0 => Ok(Platform::Chip8),
+        1 => Ok(Platform::SuperChip),
+        2 => Ok(Platform::XoChip),
+        _ => Err(StateError::InvalidField { field: "platform" }),
+    }
+}
+
+fn quirks_to_byte(quirks: Quirks) -> u8 {
+    [
+        quirks.shift_uses_vy,
+        quirks.load_store_increments_i,
+        quirks.jump_uses_vx,
+        quirks.logic_resets_vf,
+        quirks.clip_sprites,
+        quirks.display_wait,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |bits, (index, &enabled)| bits | ((enabled as u8) << index))
+}
+
+fn quirks_from_byte(bits: u8) -> Result<Quirks, StateError> {
+    if bits & !0x3F != 0 {
+        return Err(StateError::InvalidField { field: "quirks" });
+    }
+    Ok(Quirks {
+        shift_uses_vy: bits & 0x01 != 0,
+        load_store_increments_i: bits & 0x02 != 0,
+        jump_uses_vx: bits & 0x04 != 0,
+        logic_resets_vf: bits & 0x08 != 0,
+        clip_sprites: bits & 0x10 != 0,
+        display_wait: bits & 0x20 != 0,
+    })
+}
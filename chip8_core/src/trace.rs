@@ -0,0 +1,117 @@
+use crate::{Chip8, Instruction, REGISTER_COUNT};
+use std::io::{self, Write};
+use std::ops::{Range, RangeInclusive};
+
+/// Width the disassembly column is padded to, so the register columns line up
+const INSTRUCTION_WIDTH: usize = 20;
+
+/// Writes one line per executed instruction, for diffing runs against each other.
+///
+/// Call `trace` before every `Chip8::cycle` and `end_frame` after every timer
+/// update. Each line records the machine as the instruction is about to run:
+///
+/// ```text
+///      cycle PC   op   instruction          | V0 V1 .. VF                                     | I=.... SP=.. DT=.. ST=..
+///         42 0226 6A02 LD VA, 0x02          | 00 01 00 00 00 00 00 00 00 00 00 00 00 00 00 00 | I=02EA SP=01 DT=00 ST=00
+/// ```
+///
+/// The cycle count is decimal and right-aligned in ten columns; every other
+/// value is upper-case hex. `F000 NNNN` shows its address after `LONG`, and
+/// opcodes that do not decode show as `???`. The three sections are split
+/// by ` | ` so tools can pull the line apart without knowing the mnemonics.
+pub struct Tracer<W: Write> {
+    writer: W,
+    addresses: Option<RangeInclusive<u16>>,
+    frames: Option<Range<u64>>,
+    cycles: u64,
+    frame: u64,
+}
+
+impl<W: Write> Tracer<W> {
+    /// Creates a tracer that records every instruction to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            addresses: None,
+            frames: None,
+            cycles: 0,
+            frame: 0,
+        }
+    }
+
+    /// Only records instructions whose address is in `range`
+    pub fn set_address_range(&mut self, range: RangeInclusive<u16>) {
+        self.addresses = Some(range);
+    }
+
+    /// Only records instructions run during the frames in `range`, counting from 0
+    pub fn set_frames(&mut self, range: Range<u64>) {
+        self.frames = Some(range);
+    }
+
+    /// Returns the number of instructions seen so far, recorded or not
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the current frame number
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Records the instruction `chip8` is about to execute, if the filters allow it
+    pub fn trace(&mut self, chip8: &Chip8) -> io::Result<()> {
+        let cycle = self.cycles;
+        self.cycles += 1;
+
+        let pc = chip8.program_counter;
+        let in_range = self.addresses.as_ref().is_none_or(|range| range.contains(&pc));
+        let in_frames = self.frames.as_ref().is_none_or(|range| range.contains(&self.frame));
+        if !in_range || !in_frames {
+            return Ok(());
+        }
+
+        let read_word = |addr: u16| {
+            let addr = addr as usize;
+            match chip8.memory.get(addr..addr + 2) {
+                Some(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]),
+                None => 0,
+            }
+        };
+        let opcode = read_word(pc);
+        let instruction = match Instruction::decode(opcode) {
+            Ok(Instruction::LoadLongIndex) => format!("LD I, LONG 0x{:04X}", read_word(pc.wrapping_add(2))),
+            Ok(instruction) => instruction.to_string(),
+            Err(_) => "???".to_string(),
+        };
+
+        write!(
+            self.writer,
+            "{:>10} {:04X} {:04X} {:<width$} |",
+            cycle,
+            pc,
+            opcode,
+            instruction,
+            width = INSTRUCTION_WIDTH
+        )?;
+        for x in 0..REGISTER_COUNT {
+            write!(self.writer, " {:02X}", chip8.general_registers[x])?;
+        }
+        writeln!(
+            self.writer,
+            " | I={:04X} SP={:02X} DT={:02X} ST={:02X}",
+            chip8.index_register, chip8.stack_pointer, chip8.delay_timer, chip8.sound_timer
+        )
+    }
+
+    /// Moves on to the next frame
+    pub fn end_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Flushes and returns the writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
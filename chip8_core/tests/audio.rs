@@ -0,0 +1,72 @@
+use chip8_core::{Chip8, Platform, DEFAULT_PITCH};
+
+/// An XO-CHIP machine with `program` loaded and the pattern bytes at 0x300
+fn machine(program: &[u16], pattern: &[u8]) -> Chip8 {
+    let bytes: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+    let mut chip8 = Chip8::new(Platform::XoChip, Platform::XoChip.default_quirks());
+    chip8.load_program(&bytes).unwrap();
+    chip8.write_memory(0x300, pattern).unwrap();
+    chip8.set_index_register(0x300);
+    chip8
+}
+
+#[test]
+fn pitch() {
+    // The default pitch plays 4000 bits per second, and every 48 steps doubles it
+    let mut chip8 = machine(&[0xF03A, 0xF13A], &[]);
+    assert_eq!(chip8.audio_playback_rate(), 4000.0);
+    chip8.set_register(0, DEFAULT_PITCH + 48);
+    chip8.set_register(1, DEFAULT_PITCH - 48);
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.audio_playback_rate(), 8000.0);
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.audio_playback_rate(), 2000.0);
+}
+
+#[test]
+fn rendering_the_pattern() {
+    let mut pattern = [0; 16];
+    pattern[..3].copy_from_slice(&[0xFF, 0x00, 0xF0]);
+    let mut chip8 = machine(&[0xF002], &pattern);
+    chip8.cycle().unwrap();
+
+    // Silent until the sound timer runs
+    let mut samples = [1.0; 16];
+    chip8.render_audio(&mut samples, 4000);
+    assert_eq!(samples, [0.0; 16]);
+
+    // At 4000 samples per second each sample is one bit, and the next buffer carries on
+    chip8.set_sound_timer(2);
+    chip8.render_audio(&mut samples, 4000);
+    assert_eq!(samples[..8], [1.0; 8]);
+    assert_eq!(samples[8..], [-1.0; 8]);
+    let mut next = [0.0; 8];
+    chip8.render_audio(&mut next, 4000);
+    assert_eq!(next, [1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0]);
+
+    // Halving the sample rate plays every other bit
+    let mut chip8 = machine(&[0xF002], &pattern);
+    chip8.cycle().unwrap();
+    chip8.set_sound_timer(1);
+    let mut samples = [0.0; 8];
+    chip8.render_audio(&mut samples, 2000);
+    assert_eq!(samples, [1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0]);
+}
+
+#[test]
+fn buzzer_follows_the_sound_timer() {
+    let mut chip8 = Chip8::new(Platform::Chip8, Platform::Chip8.default_quirks());
+    chip8.load_program(&[0x60, 0x02, 0xF0, 0x18]).unwrap();
+    assert!(!chip8.is_buzzer_active());
+    chip8.cycle().unwrap();
+    chip8.cycle().unwrap();
+    assert!(chip8.is_buzzer_active());
+
+    // The tone stops once the timer has counted down, and CHIP-8 never has a pattern to play
+    chip8.update_timers();
+    assert!(chip8.is_buzzer_active());
+    chip8.update_timers();
+    assert_eq!(chip8.sound_timer(), 0);
+    assert!(!chip8.is_buzzer_active());
+    assert!(!chip8.has_audio_pattern());
+}
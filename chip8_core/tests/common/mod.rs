@@ -0,0 +1,22 @@
+use chip8_core::{Chip8, Platform};
+use std::fs;
+use std::path::Path;
+
+/// A machine with BRIX loaded and a fixed seed, so every run plays out the same
+pub fn brix(platform: Platform, seed: u64) -> Chip8 {
+    let rom = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("../c8games/BRIX")).unwrap();
+    let mut chip8 = Chip8::new(platform, platform.default_quirks());
+    chip8.seed_rng(seed);
+    chip8.load_program(&rom).unwrap();
+    chip8
+}
+
+/// Runs whole frames of ten cycles each
+pub fn run_frames(chip8: &mut Chip8, frames: usize) {
+    for _ in 0..frames {
+        for _ in 0..10 {
+            chip8.cycle().unwrap();
+        }
+        chip8.update_timers();
+    }
+}
@@ -0,0 +1,239 @@
+//! Conformance runner for the community CHIP-8 test suite (corax+, flags,
+//! quirks and keypad tests). Each ROM vendored into `tests/roms/` runs
+//! headlessly on every platform profile, the pass and fail images it draws are
+//! counted, and the resulting compatibility matrix is compared against
+//! `tests/conformance.txt`.
+//!
+//! Every ROM must be present: a missing one fails the run. The suite is not
+//! part of a plain `cargo test` until the ROMs are vendored, so run it with
+//! `cargo test --test conformance`, and record a new matrix with
+//! `cargo test --test conformance -- --bless`. Blessing prints each final
+//! screen so the pass and fail images found can be checked by eye.
+
+use chip8_core::{Chip8, Platform};
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+const SEED: u64 = 0x5EED;
+const CYCLES_PER_FRAME: u32 = 20;
+const FRAMES: u64 = 600;
+/// The suite reads this byte to pick a test without showing its menu. Like
+/// [`GLYPHS`], not yet checked against the real ROMs.
+const MENU_PRESET_ADDRESS: usize = 0x1FF;
+const PLATFORMS: [(Platform, &str); 3] =
+    [(Platform::Chip8, "chip8"), (Platform::SuperChip, "schip"), (Platform::XoChip, "xochip")];
+const COLUMN_WIDTH: usize = 14;
+
+/// How a ROM picks what to test
+#[derive(Clone, Copy)]
+enum Preset {
+    None,
+    /// The menu entry for the platform under test: 1 CHIP-8, 2 SUPER-CHIP, 3 XO-CHIP
+    Platform,
+    Fixed(u8),
+}
+
+struct Suite {
+    name: &'static str,
+    file: &'static str,
+    preset: Preset,
+    /// Keys held during a range of frames
+    presses: &'static [(usize, RangeInclusive<u64>)],
+}
+
+const SUITES: [Suite; 4] = [
+    Suite { name: "corax+", file: "3-corax+.ch8", preset: Preset::None, presses: &[] },
+    Suite { name: "flags", file: "4-flags.ch8", preset: Preset::None, presses: &[] },
+    Suite { name: "quirks", file: "5-quirks.ch8", preset: Preset::Platform, presses: &[] },
+    // Entry 3 is the FX0A test, which wants a key pressed and released
+    Suite { name: "keypad", file: "6-keypad.ch8", preset: Preset::Fixed(3), presses: &[(0x5, 120..=130)] },
+];
+
+/// An image the suite draws next to a test, as rows of up to eight pixels
+struct Glyph {
+    pass: bool,
+    width: usize,
+    rows: &'static [u8],
+}
+
+/// Not yet checked against the real ROMs: compare with the screens `--bless` prints
+const GLYPHS: [Glyph; 4] = [
+    // "OK" and "NO" in the suite's small font
+    Glyph { pass: true, width: 8, rows: &[0xEA, 0xAC, 0xAA, 0xEA] },
+    Glyph { pass: false, width: 8, rows: &[0xCE, 0xAA, 0xAA, 0xAE] },
+    // Check mark and cross
+    Glyph { pass: true, width: 8, rows: &[0x01, 0x02, 0x84, 0x48, 0x30] },
+    Glyph { pass: false, width: 5, rows: &[0x88, 0x50, 0x20, 0x50, 0x88] },
+];
+
+enum Outcome {
+    Missing,
+    Passed(usize),
+    Failed { failed: usize, total: usize },
+    /// The screen shows no pass or fail images
+    NoVerdict,
+    Error(String),
+}
+
+impl Outcome {
+    fn describe(&self) -> String {
+        match self {
+            Outcome::Missing => "missing".to_string(),
+            Outcome::Passed(total) => format!("pass ({})", total),
+            Outcome::Failed { failed, total } => format!("FAIL {}/{}", failed, total),
+            Outcome::NoVerdict => "no verdict".to_string(),
+            Outcome::Error(err) => format!("error: {}", err),
+        }
+    }
+}
+
+/// Counts the places on screen where a glyph is drawn exactly
+fn count_glyph(chip8: &Chip8, glyph: &Glyph) -> usize {
+    let framebuffer = chip8.get_framebuffer();
+    let lit = |x: usize, y: usize| framebuffer.pixels[x + y * framebuffer.width] != 0;
+    let height = glyph.rows.len();
+    if framebuffer.width < glyph.width || framebuffer.height < height {
+        return 0;
+    }
+
+    let mut count = 0;
+    for top in 0..=framebuffer.height - height {
+        for left in 0..=framebuffer.width - glyph.width {
+            let matches = glyph.rows.iter().enumerate().all(|(row, &bits)| {
+                (0..glyph.width).all(|col| lit(left + col, top + row) == (bits & (0x80 >> col) != 0))
+            });
+            if matches {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Draws the screen in ASCII
+fn screen(chip8: &Chip8) -> String {
+    let framebuffer = chip8.get_framebuffer();
+    let mut screen = String::new();
+    for row in framebuffer.pixels.chunks(framebuffer.width) {
+        screen.extend(row.iter().map(|&pixel| if pixel != 0 { '#' } else { '.' }));
+        screen.push('\n');
+    }
+    screen
+}
+
+/// Runs a ROM, returning the outcome and the final screen
+fn run(rom: &[u8], suite: &Suite, platform: Platform) -> (Outcome, String) {
+    let mut chip8 = Chip8::new(platform, platform.default_quirks());
+    chip8.seed_rng(SEED);
+    if let Err(err) = chip8.load_program(rom) {
+        return (Outcome::Error(err.to_string()), String::new());
+    }
+    let preset = match suite.preset {
+        Preset::None => None,
+        Preset::Platform => Some(match platform {
+            Platform::Chip8 => 1,
+            Platform::SuperChip => 2,
+            Platform::XoChip => 3,
+        }),
+        Preset::Fixed(entry) => Some(entry),
+    };
+    if let Some(entry) = preset {
+        chip8.write_memory(MENU_PRESET_ADDRESS, &[entry]).unwrap();
+    }
+
+    for frame in 0..FRAMES {
+        for key in 0..16 {
+            let pressed = suite.presses.iter().any(|(pressed, frames)| *pressed == key && frames.contains(&frame));
+            chip8.set_key_state(key, pressed);
+        }
+        for _ in 0..CYCLES_PER_FRAME {
+            if chip8.is_halted() {
+                break;
+            }
+            if let Err(err) = chip8.cycle() {
+                return (Outcome::Error(err.to_string()), screen(&chip8));
+            }
+        }
+        chip8.update_timers();
+    }
+
+    let (mut passed, mut failed) = (0, 0);
+    for glyph in &GLYPHS {
+        let count = count_glyph(&chip8, glyph);
+        if glyph.pass {
+            passed += count;
+        } else {
+            failed += count;
+        }
+    }
+    let outcome = match (passed, failed) {
+        (0, 0) => Outcome::NoVerdict,
+        (passed, 0) => Outcome::Passed(passed),
+        (passed, failed) => Outcome::Failed { failed, total: passed + failed },
+    };
+    (outcome, screen(&chip8))
+}
+
+fn main() {
+    let bless = env::args().skip(1).any(|arg| arg == "--bless");
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let rom_dir = manifest.join("tests/roms");
+    let expected_path = manifest.join("tests/conformance.txt");
+
+    let mut matrix = format!("{:<10}", "ROM");
+    for (_, name) in PLATFORMS {
+        write!(matrix, "{:<width$}", name, width = COLUMN_WIDTH).unwrap();
+    }
+    matrix = matrix.trim_end().to_string() + "\n";
+
+    let mut missing = Vec::new();
+    let mut screens = String::new();
+    for suite in &SUITES {
+        let rom = fs::read(rom_dir.join(suite.file)).ok();
+        if rom.is_none() {
+            missing.push(suite.file);
+        }
+        let mut line = format!("{:<10}", suite.name);
+        for (platform, platform_name) in PLATFORMS {
+            let outcome = match &rom {
+                Some(rom) => {
+                    let (outcome, screen) = run(rom, suite, platform);
+                    writeln!(screens, "\n{} on {}: {}\n{}", suite.name, platform_name, outcome.describe(), screen)
+                        .unwrap();
+                    outcome
+                }
+                None => Outcome::Missing,
+            };
+            write!(line, "{:<width$}", outcome.describe(), width = COLUMN_WIDTH).unwrap();
+        }
+        writeln!(matrix, "{}", line.trim_end()).unwrap();
+    }
+    print!("{}", matrix);
+
+    if !missing.is_empty() {
+        println!("\nconformance: missing {} in {} (see README.md there)", missing.join(", "), rom_dir.display());
+        std::process::exit(1);
+    }
+    if bless {
+        print!("{}", screens);
+        fs::write(&expected_path, &matrix).unwrap();
+        println!("\nconformance: recorded {}", expected_path.display());
+        return;
+    }
+    match fs::read_to_string(&expected_path) {
+        Ok(expected) if expected == matrix => println!("\nconformance: matrix unchanged"),
+        Ok(expected) => {
+            println!("\nconformance: matrix differs from {}, which expects:", expected_path.display());
+            print!("{}", expected);
+            println!("If the change is intentional, run `cargo test --test conformance -- --bless`");
+            std::process::exit(1);
+        }
+        Err(_) => {
+            println!("\nconformance: no recorded matrix, run `cargo test --test conformance -- --bless`");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use chip8_core::{Access, Chip8, Debugger, Instruction, Platform, StopReason, WatchKind};
+
+const LIMIT: u64 = 1000;
+
+/// Calls a subroutine that stores V0 at 0x300 and loads it back, then spins at 0x206
+const PROGRAM: [u16; 8] = [
+    0x6005, // 200: LD V0, 5
+    0x2208, // 202: CALL 0x208
+    0x7001, // 204: ADD V0, 1
+    0x1206, // 206: JP 0x206
+    0xA300, // 208: LD I, 0x300
+    0xF055, // 20A: LD [I], V0
+    0xF065, // 20C: LD V0, [I]
+    0x00EE, // 20E: RET
+];
+
+fn machine(program: &[u16]) -> Chip8 {
+    let bytes: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+    let mut chip8 = Chip8::new(Platform::SuperChip, Platform::SuperChip.default_quirks());
+    chip8.load_program(&bytes).unwrap();
+    chip8
+}
+
+#[test]
+fn breakpoints() {
+    let mut chip8 = machine(&PROGRAM);
+    let mut debugger = Debugger::new();
+    assert!(debugger.add_breakpoint(0x20C));
+    assert!(debugger.add_breakpoint(0x204));
+    assert!(!debugger.add_breakpoint(0x204));
+    assert_eq!(debugger.breakpoints().collect::<Vec<_>>(), [0x204, 0x20C]);
+
+    assert_eq!(debugger.resume(&mut chip8, LIMIT), StopReason::Breakpoint { pc: 0x20C });
+    assert_eq!(chip8.program_counter(), 0x20C);
+    assert_eq!(debugger.cycles(), 4);
+
+    // Resuming runs the instruction at the breakpoint instead of stopping again
+    assert_eq!(debugger.resume(&mut chip8, LIMIT), StopReason::Breakpoint { pc: 0x204 });
+    assert!(debugger.remove_breakpoint(0x204));
+    assert!(!debugger.remove_breakpoint(0x204));
+    assert_eq!(debugger.resume(&mut chip8, LIMIT), StopReason::CycleLimit);
+    assert_eq!(chip8.program_counter(), 0x206);
+}
+
+#[test]
+fn watchpoints() {
+    let mut chip8 = machine(&PROGRAM);
+    let mut debugger = Debugger::new();
+    debugger.add_watchpoint(0x301..=0x310, WatchKind::Access);
+    debugger.add_watchpoint(0x2F0..=0x300, WatchKind::Read);
+    let read = StopReason::Watchpoint { pc: 0x20C, addr: 0x300, access: Access::Read };
+    assert_eq!(debugger.resume(&mut chip8, LIMIT), read);
+    assert_eq!(chip8.program_counter(), 0x20E);
+
+    assert!(debugger.remove_watchpoint_range(0x2F0..=0x300, WatchKind::Read));
+    assert!(!debugger.remove_watchpoint_range(0x2F0..=0x300, WatchKind::Read));
+    debugger.add_watchpoint(0x300..=0x300, WatchKind::Write);
+    let mut chip8 = machine(&PROGRAM);
+    let write = StopReason::Watchpoint { pc: 0x20A, addr: 0x300, access: Access::Write };
+    assert_eq!(debugger.resume(&mut chip8, LIMIT), write);
+
+    // The stop comes after the access, so the write has already landed
+    assert_eq!(chip8.program_counter(), 0x20C);
+    assert_eq!(chip8.memory()[0x300], 5);
+    let mut chip8 = machine(&PROGRAM);
+    for _ in 0..3 {
+        assert_eq!(debugger.step(&mut chip8), StopReason::Stepped);
+    }
+    assert_eq!(debugger.step(&mut chip8), write);
+
+    assert!(debugger.remove_watchpoint(0x300));
+    assert!(debugger.remove_watchpoint(0x305));
+    assert!(!debugger.remove_watchpoint(0x300));
+    let mut chip8 = machine(&PROGRAM);
+    assert_eq!(debugger.resume(&mut chip8, LIMIT), StopReason::CycleLimit);
+}
+
+#[test]
+fn opcode_breakpoints() {
+    let mut chip8 = machine(&PROGRAM);
+    let mut debugger = Debugger::new();
+    debugger.add_opcode_breakpoint(0xF0FF, 0xF065);
+    let load = StopReason::Opcode { pc: 0x20C, instruction: Instruction::LoadRegisters { x: 0 } };
+    assert_eq!(debugger.resume(&mut chip8, LIMIT), load);
+
+    // The value is masked, so any call matches
+    debugger.clear_opcode_breakpoints();
+    debugger.add_opcode_breakpoint(0xF000, 0x2FFF);
+    let mut chip8 = machine(&PROGRAM);
+    let call = StopReason::Opcode { pc: 0x202, instruction: Instruction::Call { addr: 0x208 } };
+    assert_eq!(debugger.resume(&mut chip8, LIMIT), call);
+
+    debugger.clear_opcode_breakpoints();
+    assert_eq!(debugger.resume(&mut chip8, LIMIT), StopReason::CycleLimit);
+}
+
+#[test]
+fn step_over() {
+    let mut chip8 = machine(&PROGRAM);
+    let mut debugger = Debugger::new();
+    assert_eq!(debugger.step_over(&mut chip8, LIMIT), StopReason::Stepped);
+    assert_eq!(chip8.program_counter(), 0x202);
+
+    assert_eq!(debugger.step_over(&mut chip8, LIMIT), StopReason::Stepped);
+    assert_eq!(chip8.program_counter(), 0x204);
+    assert_eq!(chip8.stack_pointer(), 0);
+    assert_eq!(debugger.cycles(), 6);
+
+    // A breakpoint inside the subroutine still stops it
+    let mut chip8 = machine(&PROGRAM);
+    debugger.add_breakpoint(0x20C);
+    debugger.step(&mut chip8);
+    assert_eq!(debugger.step_over(&mut chip8, LIMIT), StopReason::Breakpoint { pc: 0x20C });
+}
+
+#[test]
+fn step_out() {
+    let mut chip8 = machine(&PROGRAM);
+    let mut debugger = Debugger::new();
+    assert_eq!(debugger.step_out(&mut chip8, LIMIT), StopReason::NotInSubroutine);
+    assert_eq!((chip8.program_counter(), debugger.cycles()), (0x200, 0));
+
+    debugger.step(&mut chip8);
+    debugger.step(&mut chip8);
+    assert_eq!((chip8.program_counter(), chip8.stack_pointer()), (0x208, 1));
+    assert_eq!(debugger.step_out(&mut chip8, LIMIT), StopReason::Stepped);
+    assert_eq!((chip8.program_counter(), chip8.stack_pointer()), (0x204, 0));
+    assert_eq!(chip8.register(0), 5);
+}
+
+#[test]
+fn halting_and_errors() {
+    let mut chip8 = machine(&[0x00FD]);
+    let mut debugger = Debugger::new();
+    assert_eq!(debugger.resume(&mut chip8, LIMIT), StopReason::Halted);
+
+    let mut chip8 = machine(&[0x00EE]);
+    assert!(matches!(debugger.step(&mut chip8), StopReason::Error(_)));
+}
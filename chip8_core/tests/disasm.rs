@@ -0,0 +1,101 @@
+use chip8_core::{disassemble, parse_number, Instruction, Platform, STARTING_ADDRESS};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Assembles a listing printed by `Disassembly`, resolving labels in a first pass
+fn reassemble(listing: &str) -> Vec<u8> {
+    let lines: Vec<&str> = listing
+        .lines()
+        .map(|line| line.split(';').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let mut labels = HashMap::new();
+    let mut addr = 0;
+    for line in &lines {
+        if let Some(label) = line.strip_suffix(':') {
+            labels.insert(label.to_string(), addr);
+        } else if let Some(origin) = line.strip_prefix("org ") {
+            addr = parse_number(origin).unwrap();
+        } else if let Some(data) = line.strip_prefix("db ") {
+            addr += data.split(',').count() as u16;
+        } else if line.contains("LONG ") {
+            addr += 4;
+        } else {
+            addr += 2;
+        }
+    }
+
+    let resolve = |operand: &str| match labels.get(operand) {
+        Some(addr) => format!("{:#X}", addr),
+        None => operand.to_string(),
+    };
+
+    let mut rom = Vec::new();
+    for line in &lines {
+        if line.ends_with(':') || line.starts_with("org ") {
+            continue;
+        }
+        if let Some(data) = line.strip_prefix("db ") {
+            rom.extend(data.split(',').map(|byte| parse_number(byte).unwrap() as u8));
+            continue;
+        }
+
+        let (text, long) = match line.split_once("LONG ") {
+            Some((text, target)) => (format!("{}LONG", text), Some(parse_number(&resolve(target)).unwrap())),
+            None => (line.to_string(), None),
+        };
+        let (mnemonic, operands) = text.split_once(' ').unwrap_or((&text, ""));
+        let operands: Vec<String> = operands.split(',').map(|operand| resolve(operand.trim())).collect();
+        let instruction: Instruction = format!("{} {}", mnemonic, operands.join(", ")).parse().unwrap();
+        rom.extend(instruction.encode().to_be_bytes());
+        if let Some(target) = long {
+            rom.extend(target.to_be_bytes());
+        }
+    }
+    rom
+}
+
+#[test]
+fn every_bundled_rom_round_trips() {
+    let games = Path::new(env!("CARGO_MANIFEST_DIR")).join("../c8games");
+    let mut count = 0;
+    for entry in fs::read_dir(&games).unwrap() {
+        let path = entry.unwrap().path();
+        let rom = fs::read(&path).unwrap();
+        for platform in [Platform::Chip8, Platform::XoChip] {
+            let listing = disassemble(&rom, STARTING_ADDRESS, platform).to_string();
+            assert_eq!(reassemble(&listing), rom, "{} did not round-trip", path.display());
+        }
+        count += 1;
+    }
+    assert!(count > 0, "no ROMs found in {}", games.display());
+}
+
+#[test]
+fn tracing_separates_code_from_data() {
+    // CALL 0x206, JP 0x204, data, LD I 0x20A, RET, sprite byte
+    let rom = [0x22, 0x06, 0x12, 0x04, 0xFF, 0xFF, 0xA2, 0x0A, 0x00, 0xEE, 0x3C];
+    let disassembly = disassemble(&rom, STARTING_ADDRESS, Platform::Chip8);
+
+    assert!(disassembly.is_code(0x200));
+    assert!(disassembly.is_code(0x206));
+    assert!(!disassembly.is_code(0x20A));
+    assert_eq!(disassembly.label(0x206).as_deref(), Some("sub_206"));
+    assert_eq!(disassembly.label(0x20A).as_deref(), Some("data_20A"));
+    assert_eq!(disassembly.instruction_at(0x208), Some(Instruction::Return));
+}
+
+#[test]
+fn plane_masks_round_trip() {
+    // F301 selects both planes, while F401 has no third plane to select and stays data
+    let rom = [0xF3, 0x01, 0xF4, 0x01];
+    let disassembly = disassemble(&rom, STARTING_ADDRESS, Platform::XoChip);
+    assert_eq!(disassembly.instruction_at(0x200), Some(Instruction::SelectPlanes { planes: 3 }));
+    assert_eq!(disassembly.instruction_at(0x202), None);
+    assert_eq!(reassemble(&disassembly.to_string()), rom);
+
+    assert_eq!("PLANE 3".parse::<Instruction>().unwrap().encode(), 0xF301);
+    assert!("PLANE 4".parse::<Instruction>().is_err());
+}
@@ -0,0 +1,147 @@
+use chip8_core::{serve_gdb, Chip8, Debugger, Platform};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Calls a subroutine that stores V0 at 0x300 and loads it back, then spins at 0x206
+const PROGRAM: [u16; 8] = [0x6005, 0x2208, 0x7001, 0x1206, 0xA300, 0xF055, 0xF065, 0x00EE];
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    /// Serves a fresh machine on a loopback port and connects to it
+    fn connect() -> (Client, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let bytes: Vec<u8> = PROGRAM.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+            let mut chip8 = Chip8::new(Platform::Chip8, Platform::Chip8.default_quirks());
+            chip8.load_program(&bytes).unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            serve_gdb(stream, &mut chip8, &mut Debugger::new()).unwrap();
+        });
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        stream.set_nodelay(true).unwrap();
+        (Client { stream }, server)
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    /// Reads one packet, checking its framing and checksum
+    fn read_packet(&mut self) -> String {
+        assert_eq!(self.read_byte(), b'$');
+        let mut data = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => data.push(byte),
+            }
+        }
+        let checksum = [self.read_byte(), self.read_byte()];
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(checksum, checksum_of(&data), "bad checksum on {:?}", String::from_utf8_lossy(&data));
+        String::from_utf8(data).unwrap()
+    }
+
+    /// Sends a packet and returns the reply, acknowledging both ways
+    fn request(&mut self, packet: &str) -> String {
+        write!(self.stream, "${}#{:02x}", packet, checksum_of(packet.as_bytes())).unwrap();
+        assert_eq!(self.read_byte(), b'+', "{} was not acknowledged", packet);
+        let reply = self.read_packet();
+        self.stream.write_all(b"+").unwrap();
+        reply
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+#[test]
+fn framing_and_checksums() {
+    let (mut client, server) = Client::connect();
+
+    // A packet with a bad checksum is refused and the next one still works
+    client.stream.write_all(b"$g#00").unwrap();
+    assert_eq!(client.read_byte(), b'-');
+    assert_eq!(client.request("?"), "S05");
+
+    // A NAK makes the server resend its last reply
+    client.request("p11");
+    client.stream.write_all(b"-").unwrap();
+    assert_eq!(client.read_packet(), "0002");
+    client.stream.write_all(b"+").unwrap();
+
+    let features = client.request("qSupported:swbreak+");
+    assert_eq!(features, "PacketSize=4000;qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+");
+    let xml = client.request("qXfer:features:read:target.xml:0,fff");
+    assert!(xml.starts_with('l') && xml.contains("<architecture>chip8</architecture>"), "{}", xml);
+
+    assert_eq!(client.request("D"), "OK");
+    server.join().unwrap();
+}
+
+#[test]
+fn no_ack_mode() {
+    let (mut client, server) = Client::connect();
+    assert_eq!(client.request("QStartNoAckMode"), "OK");
+    client.stream.write_all(b"$p10#d1").unwrap();
+    assert_eq!(client.read_packet(), "0000");
+    client.stream.write_all(b"$D#44").unwrap();
+    assert_eq!(client.read_packet(), "OK");
+    server.join().unwrap();
+}
+
+#[test]
+fn breakpoints_and_watchpoints() {
+    let (mut client, server) = Client::connect();
+    assert_eq!(client.request("Z0,204,2"), "OK");
+    assert_eq!(client.request("Z2,300,1"), "OK");
+
+    // The watchpoint stops after the write, with the new value in memory
+    assert_eq!(client.request("c"), "T05watch:300;");
+    assert_eq!(client.request("p11"), "0c02");
+    assert_eq!(client.request("m300,1"), "05");
+
+    assert_eq!(client.request("z2,300,1"), "OK");
+    assert_eq!(client.request("Z3,300,1"), "OK");
+    assert_eq!(client.request("c"), "T05rwatch:300;");
+    assert_eq!(client.request("z3,300,1"), "OK");
+
+    assert_eq!(client.request("c"), "T05swbreak:;");
+    assert_eq!(client.request("p11"), "0402");
+    assert_eq!(client.request("z0,204,2"), "OK");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "0602");
+
+    // Unsupported kinds get an empty reply
+    assert_eq!(client.request("Z9,300,1"), "");
+    assert_eq!(client.request("D"), "OK");
+    server.join().unwrap();
+}
+
+#[test]
+fn oversized_memory_requests() {
+    let (mut client, server) = Client::connect();
+
+    // Lengths that would overflow are clamped to the memory and the packet size
+    let reply = client.request("m1,ffffffffffffffff");
+    assert_eq!(reply.len(), (0x1000 - 1) * 2);
+    assert_eq!(client.request("m1000,1"), "E01");
+
+    assert_eq!(client.request("Mffffffffffffffff,2:0102"), "E01");
+    assert_eq!(client.request("Mfff,2:0102"), "E01");
+    assert_eq!(client.request("Mffe,2:0102"), "OK");
+    assert_eq!(client.request("mffe,2"), "0102");
+
+    assert_eq!(client.request("D"), "OK");
+    server.join().unwrap();
+}
@@ -0,0 +1,213 @@
+//! Golden-image regression suite: runs every ROM in `c8games/` with a fixed
+//! seed and a per-ROM key script, hashes the screen at checkpoints and compares
+//! the hashes to `tests/golden/<ROM>.txt`.
+//!
+//! `cargo test --test golden` checks, `cargo test --test golden -- --bless`
+//! rewrites the goldens after an intentional change. Any other arguments pick
+//! ROMs by name.
+
+use chip8_core::{Chip8, Platform};
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SEED: u64 = 0x5EED;
+/// Frames after which the screen is hashed
+const CHECKPOINTS: [u64; 6] = [30, 60, 120, 300, 600, 1200];
+
+/// How a ROM is played. `keys` is a timeline with one slot every `period`
+/// frames, each slot a key held for `hold` frames or `.` for none. The
+/// timeline repeats until the run ends.
+struct Script {
+    rom: &'static str,
+    keys: &'static str,
+    period: u64,
+    hold: u64,
+    cycles_per_frame: u32,
+    clip_sprites: bool,
+}
+
+impl Script {
+    const fn new(rom: &'static str, keys: &'static str, period: u64, hold: u64) -> Self {
+        Script { rom, keys, period, hold, cycles_per_frame: 10, clip_sprites: false }
+    }
+
+    const fn cycles_per_frame(self, cycles_per_frame: u32) -> Self {
+        Script { cycles_per_frame, ..self }
+    }
+
+    const fn clip_sprites(self) -> Self {
+        Script { clip_sprites: true, ..self }
+    }
+
+    /// The key held during a frame, if any
+    fn key(&self, frame: u64) -> Option<usize> {
+        if self.keys.is_empty() || frame % self.period >= self.hold {
+            return None;
+        }
+        let slot = self.keys.as_bytes()[(frame / self.period) as usize % self.keys.len()];
+        (slot as char).to_digit(16).map(|key| key as usize)
+    }
+}
+
+const SCRIPTS: [Script; 23] = [
+    // Moves every tile in turn
+    Script::new("15PUZZLE", "..0123456789ABCDEF", 20, 5),
+    // The maze takes a long time to draw at the default speed
+    Script::new("BLINKY", "3678", 30, 20).cycles_per_frame(30),
+    // With wrapping the plane hits a building straight away
+    Script::new("BLITZ", ".5", 25, 3).clip_sprites(),
+    Script::new("BRIX", "4466", 25, 20),
+    Script::new("CONNECT4", "..5654565", 20, 5),
+    Script::new("GUESS", ".5F", 20, 5),
+    Script::new("HIDDEN", "..65656585", 20, 5),
+    Script::new("INVADERS", "54565", 30, 25),
+    // Draws with the direction keys, then 0 plays the pattern back
+    Script::new("KALEID", "....628462684260", 10, 4),
+    // Draws a random maze and takes no input
+    Script::new("MAZE", "", 1, 0),
+    // Repeats each sequence the game shows, for seven rounds
+    Script::new(
+        "MERLIN",
+        concat!(
+            "................8..8..5..4....................5..5..7..5..8......",
+            "................5..7..4..5..5..7.......................",
+        ),
+        10,
+        4,
+    ),
+    Script::new("MISSILE", ".8", 30, 5),
+    Script::new("PONG", ".14CD", 30, 25),
+    Script::new("PONG2", ".14CD", 30, 25),
+    Script::new("PUZZLE", ".2468", 20, 5),
+    // E starts the game without a border, then the direction keys steer
+    Script::new("SYZYGY", ".E86", 40, 30),
+    Script::new("TANK", ".258654", 30, 20),
+    Script::new("TETRIS", ".4567", 20, 5),
+    Script::new("TICTAC", ".123456789", 20, 5),
+    Script::new("UFO", ".456", 60, 5),
+    Script::new("VBRIX", ".7141", 30, 20),
+    Script::new("VERS", ".17AB2FCD", 30, 20),
+    Script::new("WIPEOFF", ".46", 30, 25),
+];
+
+/// FNV-1a over the display size and pixels
+fn hash_screen(chip8: &Chip8) -> u64 {
+    let framebuffer = chip8.get_framebuffer();
+    let size = [framebuffer.width as u8, framebuffer.height as u8];
+    size.iter()
+        .chain(framebuffer.pixels)
+        .fold(0xCBF2_9CE4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3))
+}
+
+/// Runs a ROM through every checkpoint, returning the golden file contents
+fn run(rom: &[u8], script: &Script) -> String {
+    let mut quirks = Platform::Chip8.default_quirks();
+    quirks.clip_sprites = script.clip_sprites;
+    let mut chip8 = Chip8::new(Platform::Chip8, quirks);
+    chip8.seed_rng(SEED);
+    if let Err(err) = chip8.load_program(rom) {
+        return format!("load error: {}\n", err);
+    }
+
+    let mut results = String::new();
+    let last = CHECKPOINTS[CHECKPOINTS.len() - 1];
+    for frame in 0..last {
+        for key in 0..16 {
+            chip8.set_key_state(key, script.key(frame) == Some(key));
+        }
+        for _ in 0..script.cycles_per_frame {
+            if chip8.is_halted() {
+                break;
+            }
+            if let Err(err) = chip8.cycle() {
+                writeln!(results, "error in frame {}: {}", frame, err).unwrap();
+                return results;
+            }
+        }
+        chip8.update_timers();
+
+        if CHECKPOINTS.contains(&(frame + 1)) {
+            writeln!(results, "frame {} {:016x}", frame + 1, hash_screen(&chip8)).unwrap();
+        }
+    }
+    results
+}
+
+fn main() {
+    let mut bless = false;
+    let mut filters = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            // Options cargo passes to every test binary
+            _ if arg.starts_with("--") => {}
+            _ => filters.push(arg),
+        }
+    }
+
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let golden_dir = manifest.join("tests/golden");
+    let mut roms: Vec<PathBuf> = fs::read_dir(manifest.join("../c8games"))
+        .expect("c8games directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .collect();
+    roms.sort();
+
+    let mut failures = Vec::new();
+    let mut checked = 0;
+    for path in &roms {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if !filters.is_empty() && !filters.iter().any(|filter| name.contains(filter.as_str())) {
+            continue;
+        }
+        checked += 1;
+
+        let Some(script) = SCRIPTS.iter().find(|script| script.rom == name) else {
+            println!("{} ... FAILED (no key script)", name);
+            failures.push(name);
+            continue;
+        };
+        let actual = run(&fs::read(path).unwrap(), script);
+        let golden_path = golden_dir.join(format!("{}.txt", name));
+        if bless {
+            fs::create_dir_all(&golden_dir).unwrap();
+            fs::write(&golden_path, &actual).unwrap();
+            println!("blessed {}", name);
+            continue;
+        }
+
+        match fs::read_to_string(&golden_path) {
+            Ok(expected) if expected == actual => println!("{} ... ok", name),
+            Ok(expected) => {
+                println!("{} ... FAILED", name);
+                let (mut expected_lines, mut actual_lines) = (expected.lines(), actual.lines());
+                loop {
+                    let (expected_line, actual_line) = (expected_lines.next(), actual_lines.next());
+                    if expected_line.is_none() && actual_line.is_none() {
+                        break;
+                    }
+                    if expected_line != actual_line {
+                        println!("    expected: {}", expected_line.unwrap_or("<nothing>"));
+                        println!("      actual: {}", actual_line.unwrap_or("<nothing>"));
+                    }
+                }
+                failures.push(name);
+            }
+            Err(_) => {
+                println!("{} ... FAILED (no golden file, run with --bless)", name);
+                failures.push(name);
+            }
+        }
+    }
+
+    if failures.is_empty() {
+        println!("\ngolden: {} ROMs ok", checked);
+    } else {
+        println!("\ngolden: {} of {} ROMs differ: {}", failures.len(), checked, failures.join(", "));
+        println!("If the change is intentional, run `cargo test --test golden -- --bless`");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,114 @@
+use chip8_core::{crc32, encode_ascii, encode_pbm, encode_png, Framebuffer, PALETTE};
+
+/// Splits a PNG into its chunks, checking the signature and every CRC
+fn chunks(png: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let mut chunks = Vec::new();
+    let mut rest = &png[8..];
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let (body, tail) = rest[4..].split_at(4 + length);
+        let crc = u32::from_be_bytes(tail[..4].try_into().unwrap());
+        assert_eq!(crc, crc32(body), "bad CRC on {}", String::from_utf8_lossy(&body[..4]));
+        chunks.push((body[..4].try_into().unwrap(), &body[4..]));
+        rest = &tail[4..];
+    }
+    chunks
+}
+
+/// Unpacks a zlib stream of stored blocks, returning the data and the number of blocks
+fn inflate_stored(stream: &[u8]) -> (Vec<u8>, usize) {
+    assert_eq!(&stream[..2], [0x78, 0x01]);
+    let mut data = Vec::new();
+    let mut blocks = 0;
+    let mut rest = &stream[2..];
+    loop {
+        let header = rest[0];
+        let length = u16::from_le_bytes([rest[1], rest[2]]);
+        assert_eq!(header & !1, 0, "only stored blocks are written");
+        assert_eq!(!length, u16::from_le_bytes([rest[3], rest[4]]));
+        data.extend_from_slice(&rest[5..5 + length as usize]);
+        rest = &rest[5 + length as usize..];
+        blocks += 1;
+        if header & 1 != 0 {
+            break;
+        }
+    }
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    assert_eq!(rest, ((b << 16) | a).to_be_bytes());
+    (data, blocks)
+}
+
+/// A screen with a different colour index in each column
+fn stripes(width: usize, height: usize) -> Vec<u8> {
+    (0..width * height).map(|index| (index % width % 4) as u8).collect()
+}
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(&[]), 0);
+}
+
+#[test]
+fn png_chunks() {
+    let pixels = stripes(64, 32);
+    let framebuffer = Framebuffer { width: 64, height: 32, pixels: &pixels };
+    let png = encode_png(&framebuffer, 2);
+    let chunks = chunks(&png);
+    let kinds: Vec<_> = chunks.iter().map(|(kind, _)| kind).collect();
+    assert_eq!(kinds, [b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+    assert_eq!(chunks[0].1, [0, 0, 0, 128, 0, 0, 0, 64, 8, 3, 0, 0, 0]);
+    assert_eq!(chunks[1].1, PALETTE.as_flattened());
+
+    // Every row is doubled in both directions and starts with filter type 0
+    let (data, blocks) = inflate_stored(chunks[2].1);
+    assert_eq!(blocks, 1);
+    let row: Vec<u8> = std::iter::once(0).chain((0..128).map(|x| (x / 2 % 4) as u8)).collect();
+    assert_eq!(data, row.repeat(64));
+}
+
+#[test]
+fn png_over_one_stored_block() {
+    // 128x64 at scale 3 is 192 rows of 385 bytes, which needs two blocks
+    let pixels = stripes(128, 64);
+    let framebuffer = Framebuffer { width: 128, height: 64, pixels: &pixels };
+    let png = encode_png(&framebuffer, 3);
+    let chunks = chunks(&png);
+    let (data, blocks) = inflate_stored(chunks[2].1);
+    assert_eq!(data.len(), 192 * 385);
+    assert_eq!(blocks, 2);
+    assert!(data.chunks(385).all(|row| row[0] == 0 && row[1..4] == [0; 3] && row[382..] == [3; 3]));
+}
+
+#[test]
+fn pbm_packing() {
+    // A width of 10 pads each row out to two bytes
+    let mut pixels = vec![0; 10 * 2];
+    pixels[0] = 1;
+    pixels[9] = 2;
+    pixels[10 + 8] = 3;
+    let framebuffer = Framebuffer { width: 10, height: 2, pixels: &pixels };
+    let mut expected = b"P4\n10 2\n".to_vec();
+    expected.extend_from_slice(&[0x80, 0x40, 0x00, 0x80]);
+    assert_eq!(encode_pbm(&framebuffer, 1), expected);
+
+    // At scale 3 a row is 30 pixels, still padded to whole bytes
+    let mut expected = b"P4\n30 6\n".to_vec();
+    for row in [[0xE0, 0x00, 0x00, 0x1C], [0x00, 0x00, 0x00, 0xE0]] {
+        for _ in 0..3 {
+            expected.extend_from_slice(&row);
+        }
+    }
+    assert_eq!(encode_pbm(&framebuffer, 3), expected);
+}
+
+#[test]
+fn ascii_glyphs() {
+    let pixels = [0, 1, 2, 3, 3, 2, 1, 0];
+    let framebuffer = Framebuffer { width: 4, height: 2, pixels: &pixels };
+    assert_eq!(encode_ascii(&framebuffer), b".#+@\n@+#.\n");
+}
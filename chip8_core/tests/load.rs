@@ -0,0 +1,80 @@
+use chip8_core::{Chip8, LoadError, LoadInfo, Platform};
+
+fn machine(platform: Platform) -> Chip8 {
+    Chip8::new(platform, platform.default_quirks())
+}
+
+#[test]
+fn load_info() {
+    let mut chip8 = machine(Platform::Chip8);
+    let info = chip8.load_program(&[0x12, 0x00, 0xAB]).unwrap();
+    let expected = LoadInfo { start_address: 0x200, end_address: 0x203, bytes_loaded: 3, bytes_remaining: 0xDFD };
+    assert_eq!(info, expected);
+    assert_eq!(chip8.memory()[0x200..0x203], [0x12, 0x00, 0xAB]);
+
+    // A segment that ends exactly at the end of memory leaves nothing remaining
+    let info = chip8.load_program_at(0xFFE, &[1, 2]).unwrap();
+    assert_eq!((info.end_address, info.bytes_remaining), (0x1000, 0));
+
+    // XO-CHIP has 64 KiB to load into
+    let mut chip8 = machine(Platform::XoChip);
+    let info = chip8.load_program_at(0xF000, &[0; 0x100]).unwrap();
+    assert_eq!(info.bytes_remaining, 0xF00);
+}
+
+#[test]
+fn load_errors() {
+    let mut chip8 = machine(Platform::Chip8);
+    assert_eq!(chip8.load_program_at(0x1000, &[1]), Err(LoadError::AddressOutOfRange { address: 0x1000 }));
+    assert_eq!(chip8.load_program_at(0x1000, &[]), Err(LoadError::AddressOutOfRange { address: 0x1000 }));
+    assert_eq!(chip8.load_program_at(0xFFE, &[1, 2, 3]), Err(LoadError::TooLarge { size: 3, available: 2 }));
+    assert_eq!(
+        chip8.load_program(&[0; 0xE01]),
+        Err(LoadError::TooLarge { size: 0xE01, available: 0xE00 })
+    );
+    assert_eq!(
+        LoadError::TooLarge { size: 3, available: 2 }.to_string(),
+        "program size (3 bytes) exceeds available memory space (2 bytes)"
+    );
+}
+
+#[test]
+fn segments() {
+    let mut chip8 = machine(Platform::XoChip);
+    let infos = chip8.load_segments(&[(0x200, &[1, 2]), (0x202, &[3]), (0x8000, &[4])]).unwrap();
+    let starts: Vec<_> = infos.iter().map(|info| info.start_address).collect();
+    assert_eq!(starts, [0x200, 0x202, 0x8000]);
+    assert_eq!(chip8.memory()[0x200..0x203], [1, 2, 3]);
+    assert_eq!(chip8.memory()[0x8000], 4);
+}
+
+#[test]
+fn failed_segments_leave_memory_untouched() {
+    let mut chip8 = machine(Platform::Chip8);
+    let before = chip8.memory().to_vec();
+    let overlap = chip8.load_segments(&[(0x200, &[1, 2, 3, 4]), (0x300, &[5]), (0x202, &[6, 7, 8, 9])]);
+    assert_eq!(overlap, Err(LoadError::Overlap { address: 0x202 }));
+    assert_eq!(chip8.memory(), before);
+
+    // The overlap is reported at the first shared address, whichever segment comes first
+    let overlap = chip8.load_segments(&[(0x204, &[1, 2]), (0x200, &[3; 5])]);
+    assert_eq!(overlap, Err(LoadError::Overlap { address: 0x204 }));
+
+    let too_large = chip8.load_segments(&[(0x200, &[1]), (0xFFF, &[2, 3])]);
+    assert_eq!(too_large, Err(LoadError::TooLarge { size: 2, available: 1 }));
+    assert_eq!(chip8.memory(), before);
+}
+
+#[test]
+fn empty_segments() {
+    // An empty segment writes nothing, so it can sit inside or next to another one
+    let mut chip8 = machine(Platform::Chip8);
+    let infos = chip8.load_segments(&[(0x200, &[1, 2, 3]), (0x201, &[]), (0x201, &[])]).unwrap();
+    let empty = LoadInfo { start_address: 0x201, end_address: 0x201, bytes_loaded: 0, bytes_remaining: 0xDFF };
+    assert_eq!(infos[1], empty);
+    assert_eq!(chip8.memory()[0x200..0x203], [1, 2, 3]);
+
+    // It still has to start inside memory
+    let out_of_range = chip8.load_segments(&[(0x200, &[1]), (0x1000, &[])]);
+    assert_eq!(out_of_range, Err(LoadError::AddressOutOfRange { address: 0x1000 }));
+}
@@ -0,0 +1,805 @@
+use chip8_core::{
+    Chip8, Chip8Error, FlagStorage, MemoryFlagStorage, Platform, Quirks, RandomSource, XorShiftRng, DEFAULT_SEED,
+    RPL_FLAG_COUNT, STARTING_ADDRESS,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Sets up a machine, loads a few opcodes at the start address and runs them.
+///
+/// Quirks default to `Quirks::default()` whatever the platform, so each test
+/// only opts into the behaviour it checks.
+struct Test {
+    platform: Platform,
+    quirks: Quirks,
+    registers: Vec<(usize, u8)>,
+    index: u16,
+    memory: Vec<(usize, Vec<u8>)>,
+    keys: Vec<usize>,
+    delay_timer: u8,
+}
+
+impl Test {
+    fn new() -> Self {
+        Self {
+            platform: Platform::Chip8,
+            quirks: Quirks::default(),
+            registers: Vec::new(),
+            index: 0,
+            memory: Vec::new(),
+            keys: Vec::new(),
+            delay_timer: 0,
+        }
+    }
+
+    fn platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    fn register(mut self, x: usize, value: u8) -> Self {
+        self.registers.push((x, value));
+        self
+    }
+
+    fn index(mut self, value: u16) -> Self {
+        self.index = value;
+        self
+    }
+
+    fn memory(mut self, addr: usize, bytes: &[u8]) -> Self {
+        self.memory.push((addr, bytes.to_vec()));
+        self
+    }
+
+    fn key(mut self, key: usize) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    fn delay_timer(mut self, value: u8) -> Self {
+        self.delay_timer = value;
+        self
+    }
+
+    /// Builds the machine with `program` loaded and nothing run yet
+    fn build(self, program: &[u16]) -> Chip8 {
+        let mut chip8 = Chip8::new(self.platform, self.quirks);
+        let bytes: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+        chip8.load_program(&bytes).unwrap();
+        for (x, value) in self.registers {
+            chip8.set_register(x, value);
+        }
+        chip8.set_index_register(self.index);
+        for (addr, bytes) in self.memory {
+            chip8.write_memory(addr, &bytes).unwrap();
+        }
+        for key in self.keys {
+            chip8.set_key_state(key, true);
+        }
+        chip8.set_delay_timer(self.delay_timer);
+        chip8
+    }
+
+    /// Runs the first instruction of `program` and returns the machine
+    fn run(self, program: &[u16]) -> Chip8 {
+        self.run_cycles(program, 1)
+    }
+
+    /// Runs `cycles` instructions of `program` and returns the machine
+    fn run_cycles(self, program: &[u16], cycles: usize) -> Chip8 {
+        let mut chip8 = self.build(program);
+        for _ in 0..cycles {
+            chip8.cycle().unwrap();
+        }
+        chip8
+    }
+}
+
+fn pixel(chip8: &Chip8, x: usize, y: usize) -> u8 {
+    let framebuffer = chip8.get_framebuffer();
+    framebuffer.pixels[x + y * framebuffer.width]
+}
+
+fn lit_pixels(chip8: &Chip8) -> usize {
+    chip8.get_framebuffer().pixels.iter().filter(|&&pixel| pixel != 0).count()
+}
+
+const NEXT: u16 = STARTING_ADDRESS + 2;
+const SKIPPED: u16 = STARTING_ADDRESS + 4;
+
+#[test]
+fn clear_screen() {
+    // Draw the 0 glyph, then clear
+    let chip8 = Test::new().run_cycles(&[0xD005, 0x00E0], 2);
+    assert_eq!(lit_pixels(&chip8), 0);
+}
+
+#[test]
+fn call_and_return() {
+    let chip8 = Test::new().run(&[0x2208, 0, 0, 0, 0x00EE]);
+    assert_eq!(chip8.program_counter(), 0x208);
+    assert_eq!(chip8.call_stack(), &[NEXT]);
+
+    let chip8 = Test::new().run_cycles(&[0x2208, 0, 0, 0, 0x00EE], 2);
+    assert_eq!(chip8.program_counter(), NEXT);
+    assert_eq!(chip8.stack_pointer(), 0);
+}
+
+#[test]
+fn return_with_empty_stack_fails() {
+    let mut chip8 = Test::new().build(&[0x00EE]);
+    assert_eq!(chip8.cycle(), Err(Chip8Error::StackUnderflow));
+}
+
+#[test]
+fn call_stack_overflow_fails() {
+    // A subroutine that calls itself forever
+    let mut chip8 = Test::new().build(&[0x2200]);
+    let result = (0..100).try_for_each(|_| chip8.cycle());
+    assert_eq!(result, Err(Chip8Error::StackOverflow));
+}
+
+#[test]
+fn jump() {
+    let chip8 = Test::new().run(&[0x1ABC]);
+    assert_eq!(chip8.program_counter(), 0xABC);
+}
+
+#[test]
+fn skip_if_equal_byte() {
+    assert_eq!(Test::new().register(3, 0x42).run(&[0x3342]).program_counter(), SKIPPED);
+    assert_eq!(Test::new().register(3, 0x41).run(&[0x3342]).program_counter(), NEXT);
+}
+
+#[test]
+fn skip_if_not_equal_byte() {
+    assert_eq!(Test::new().register(3, 0x41).run(&[0x4342]).program_counter(), SKIPPED);
+    assert_eq!(Test::new().register(3, 0x42).run(&[0x4342]).program_counter(), NEXT);
+}
+
+#[test]
+fn skip_if_registers_equal() {
+    let equal = Test::new().register(1, 7).register(2, 7);
+    assert_eq!(equal.run(&[0x5120]).program_counter(), SKIPPED);
+    let different = Test::new().register(1, 7).register(2, 8);
+    assert_eq!(different.run(&[0x5120]).program_counter(), NEXT);
+}
+
+#[test]
+fn skip_if_registers_not_equal() {
+    let different = Test::new().register(1, 7).register(2, 8);
+    assert_eq!(different.run(&[0x9120]).program_counter(), SKIPPED);
+    let equal = Test::new().register(1, 7).register(2, 7);
+    assert_eq!(equal.run(&[0x9120]).program_counter(), NEXT);
+}
+
+#[test]
+fn load_byte() {
+    let chip8 = Test::new().run(&[0x6A5F]);
+    assert_eq!(chip8.register(0xA), 0x5F);
+    assert_eq!(chip8.program_counter(), NEXT);
+}
+
+#[test]
+fn add_byte_wraps_without_carry() {
+    let chip8 = Test::new().register(2, 0xFF).register(0xF, 0x55).run(&[0x7202]);
+    assert_eq!(chip8.register(2), 0x01);
+    assert_eq!(chip8.register(0xF), 0x55);
+}
+
+#[test]
+fn move_register() {
+    let chip8 = Test::new().register(4, 0x99).run(&[0x8340]);
+    assert_eq!(chip8.register(3), 0x99);
+}
+
+#[test]
+fn logic_operations() {
+    let test = || Test::new().register(0, 0b1100).register(1, 0b1010).register(0xF, 9);
+    let or = test().run(&[0x8011]);
+    assert_eq!(or.register(0), 0b1110);
+    assert_eq!(or.register(0xF), 9);
+    assert_eq!(test().run(&[0x8012]).register(0), 0b1000);
+    assert_eq!(test().run(&[0x8013]).register(0), 0b0110);
+}
+
+#[test]
+fn logic_operations_reset_vf_quirk() {
+    let quirks = Quirks { logic_resets_vf: true, ..Quirks::default() };
+    for opcode in [0x8011, 0x8012, 0x8013] {
+        let chip8 = Test::new().quirks(quirks).register(0xF, 9).run(&[opcode]);
+        assert_eq!(chip8.register(0xF), 0, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn add_registers_sets_carry() {
+    let chip8 = Test::new().register(0, 0xF0).register(1, 0x20).run(&[0x8014]);
+    assert_eq!(chip8.register(0), 0x10);
+    assert_eq!(chip8.register(0xF), 1);
+
+    let chip8 = Test::new().register(0, 0x10).register(1, 0x20).register(0xF, 1).run(&[0x8014]);
+    assert_eq!(chip8.register(0), 0x30);
+    assert_eq!(chip8.register(0xF), 0);
+}
+
+#[test]
+fn add_into_vf_keeps_the_flag() {
+    // The carry is written after the sum, so VF ends up holding the flag
+    let chip8 = Test::new().register(0xF, 0xFF).register(1, 0x02).run(&[0x8F14]);
+    assert_eq!(chip8.register(0xF), 1);
+}
+
+#[test]
+fn subtract_sets_not_borrow() {
+    let chip8 = Test::new().register(0, 0x30).register(1, 0x10).run(&[0x8015]);
+    assert_eq!(chip8.register(0), 0x20);
+    assert_eq!(chip8.register(0xF), 1);
+
+    let chip8 = Test::new().register(0, 0x10).register(1, 0x30).run(&[0x8015]);
+    assert_eq!(chip8.register(0), 0xE0);
+    assert_eq!(chip8.register(0xF), 0);
+
+    // Equal values do not borrow
+    let chip8 = Test::new().register(0, 0x10).register(1, 0x10).run(&[0x8015]);
+    assert_eq!(chip8.register(0), 0);
+    assert_eq!(chip8.register(0xF), 1);
+}
+
+#[test]
+fn reverse_subtract_sets_not_borrow() {
+    let chip8 = Test::new().register(0, 0x10).register(1, 0x30).run(&[0x8017]);
+    assert_eq!(chip8.register(0), 0x20);
+    assert_eq!(chip8.register(0xF), 1);
+
+    let chip8 = Test::new().register(0, 0x30).register(1, 0x10).run(&[0x8017]);
+    assert_eq!(chip8.register(0), 0xE0);
+    assert_eq!(chip8.register(0xF), 0);
+}
+
+#[test]
+fn shifts_in_place() {
+    let chip8 = Test::new().register(0, 0b1000_0011).register(1, 0xFF).run(&[0x8016]);
+    assert_eq!(chip8.register(0), 0b0100_0001);
+    assert_eq!(chip8.register(0xF), 1);
+
+    let chip8 = Test::new().register(0, 0b1000_0010).register(1, 0xFF).run(&[0x801E]);
+    assert_eq!(chip8.register(0), 0b0000_0100);
+    assert_eq!(chip8.register(0xF), 1);
+
+    let chip8 = Test::new().register(0, 0b0100_0010).run(&[0x8016]);
+    assert_eq!(chip8.register(0xF), 0);
+}
+
+#[test]
+fn shifts_read_vy_quirk() {
+    let quirks = Quirks { shift_uses_vy: true, ..Quirks::default() };
+    let chip8 = Test::new().quirks(quirks).register(0, 0xFF).register(1, 0b0000_0110).run(&[0x8016]);
+    assert_eq!(chip8.register(0), 0b0000_0011);
+    assert_eq!(chip8.register(0xF), 0);
+
+    let chip8 = Test::new().quirks(quirks).register(0, 0).register(1, 0b1000_0001).run(&[0x801E]);
+    assert_eq!(chip8.register(0), 0b0000_0010);
+    assert_eq!(chip8.register(0xF), 1);
+}
+
+#[test]
+fn load_index() {
+    assert_eq!(Test::new().run(&[0xA123]).index_register(), 0x123);
+}
+
+#[test]
+fn jump_with_offset() {
+    let chip8 = Test::new().register(0, 0x10).register(3, 0x20).run(&[0xB300]);
+    assert_eq!(chip8.program_counter(), 0x310);
+
+    let quirks = Quirks { jump_uses_vx: true, ..Quirks::default() };
+    let chip8 = Test::new().quirks(quirks).register(0, 0x10).register(3, 0x20).run(&[0xB300]);
+    assert_eq!(chip8.program_counter(), 0x320);
+}
+
+#[test]
+fn random_is_masked() {
+    let mut chip8 = Test::new().build(&[0xC00F; 32]);
+    for _ in 0..32 {
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.register(0) & 0xF0, 0);
+    }
+}
+
+/// Always returns the same byte
+struct FixedRandom(u8);
+
+impl RandomSource for FixedRandom {
+    fn next_byte(&mut self) -> u8 {
+        self.0
+    }
+
+    fn state(&self) -> u64 {
+        self.0 as u64
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.0 = state as u8;
+    }
+}
+
+#[test]
+fn random_source_is_injectable() {
+    let mut chip8 = Test::new().build(&[0xC03C]);
+    chip8.set_random_source(Box::new(FixedRandom(0xA5)));
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.register(0), 0x24);
+}
+
+#[test]
+fn random_seeds_replay() {
+    let bytes = |seed: u64| {
+        let mut chip8 = Test::new().build(&[0xC0FF, 0xC1FF, 0xC2FF, 0xC3FF]);
+        chip8.seed_rng(seed);
+        for _ in 0..4 {
+            chip8.cycle().unwrap();
+        }
+        [chip8.register(0), chip8.register(1), chip8.register(2), chip8.register(3)]
+    };
+    assert_eq!(bytes(1), bytes(1));
+    assert_ne!(bytes(1), bytes(2));
+
+    // A new machine uses the default seed, and a zero seed falls back to it
+    let mut chip8 = Test::new().build(&[0xC0FF]);
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.register(0), bytes(DEFAULT_SEED)[0]);
+    assert_eq!(bytes(0), bytes(DEFAULT_SEED));
+
+    // The generator state can be captured part way through and restored
+    let mut rng = XorShiftRng::new(5);
+    rng.next_byte();
+    let state = rng.state();
+    let ahead: Vec<u8> = (0..4).map(|_| rng.next_byte()).collect();
+    rng.set_state(state);
+    assert_eq!((0..4).map(|_| rng.next_byte()).collect::<Vec<_>>(), ahead);
+}
+
+#[test]
+fn draw_sets_pixels_and_reports_collisions() {
+    // The font glyph for 0 is F0 90 90 90 F0
+    let chip8 = Test::new().register(1, 2).register(2, 3).run(&[0xD125]);
+    assert_eq!(lit_pixels(&chip8), 14);
+    assert_eq!(pixel(&chip8, 2, 3), 1);
+    assert_eq!(pixel(&chip8, 5, 3), 1);
+    assert_eq!(pixel(&chip8, 3, 4), 0);
+    assert_eq!(chip8.register(0xF), 0);
+
+    // Drawing the same sprite again erases it
+    let chip8 = Test::new().run_cycles(&[0xD005, 0xD005], 2);
+    assert_eq!(lit_pixels(&chip8), 0);
+    assert_eq!(chip8.register(0xF), 1);
+}
+
+#[test]
+fn draw_wraps_at_the_edges() {
+    let chip8 = Test::new().register(0, 62).register(1, 30).run(&[0xD015]);
+    assert_eq!(pixel(&chip8, 62, 30), 1);
+    assert_eq!(pixel(&chip8, 1, 30), 1);
+    assert_eq!(pixel(&chip8, 62, 0), 1);
+
+    // The starting position wraps too
+    let chip8 = Test::new().register(0, 64 + 2).register(1, 32 + 3).run(&[0xD015]);
+    assert_eq!(pixel(&chip8, 2, 3), 1);
+}
+
+#[test]
+fn draw_clips_at_the_edges_quirk() {
+    let quirks = Quirks { clip_sprites: true, ..Quirks::default() };
+    let chip8 = Test::new().quirks(quirks).register(0, 62).register(1, 30).run(&[0xD015]);
+    assert_eq!(pixel(&chip8, 62, 30), 1);
+    assert_eq!(pixel(&chip8, 1, 30), 0);
+    assert_eq!(pixel(&chip8, 62, 0), 0);
+    assert_eq!(lit_pixels(&chip8), 3);
+}
+
+#[test]
+fn draw_waits_for_the_next_frame_quirk() {
+    let quirks = Quirks { display_wait: true, ..Quirks::default() };
+    let mut chip8 = Test::new().quirks(quirks).build(&[0xD005, 0xD005]);
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.program_counter(), NEXT);
+
+    // The second draw repeats until a frame has passed
+    chip8.cycle().unwrap();
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.program_counter(), NEXT);
+    assert_eq!(lit_pixels(&chip8), 14);
+
+    chip8.update_timers();
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.program_counter(), SKIPPED);
+    assert_eq!(lit_pixels(&chip8), 0);
+}
+
+#[test]
+fn skip_if_key() {
+    let pressed = Test::new().register(5, 0xB).key(0xB);
+    assert_eq!(pressed.run(&[0xE59E]).program_counter(), SKIPPED);
+    assert_eq!(Test::new().register(5, 0xB).run(&[0xE59E]).program_counter(), NEXT);
+
+    let pressed = Test::new().register(5, 0xB).key(0xB);
+    assert_eq!(pressed.run(&[0xE5A1]).program_counter(), NEXT);
+    assert_eq!(Test::new().register(5, 0xB).run(&[0xE5A1]).program_counter(), SKIPPED);
+}
+
+#[test]
+fn wait_for_key_repeats_until_pressed() {
+    let mut chip8 = Test::new().build(&[0xF30A]);
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.program_counter(), STARTING_ADDRESS);
+    }
+
+    chip8.set_key_state(0xC, true);
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.program_counter(), NEXT);
+    assert_eq!(chip8.register(3), 0xC);
+}
+
+#[test]
+fn repeated_instructions_at_the_top_of_memory() {
+    // Fetching the last word of XO-CHIP memory wraps PC to 0 before the repeat steps back
+    let mut chip8 = Test::new().platform(Platform::XoChip).build(&[]);
+    chip8.write_memory(0xFFFE, &[0xF3, 0x0A]).unwrap();
+    chip8.set_program_counter(0xFFFE);
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.program_counter(), 0xFFFE);
+
+    let quirks = Quirks { display_wait: true, ..Quirks::default() };
+    let mut chip8 = Test::new().platform(Platform::XoChip).quirks(quirks).build(&[]);
+    chip8.write_memory(0xFFFC, &[0xD0, 0x01, 0xD0, 0x01]).unwrap();
+    chip8.set_program_counter(0xFFFC);
+    chip8.cycle().unwrap();
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.program_counter(), 0xFFFE);
+}
+
+#[test]
+fn timers() {
+    let chip8 = Test::new().delay_timer(0x33).run(&[0xF407]);
+    assert_eq!(chip8.register(4), 0x33);
+
+    let chip8 = Test::new().register(4, 0x44).run(&[0xF415]);
+    assert_eq!(chip8.delay_timer(), 0x44);
+
+    let chip8 = Test::new().register(4, 0x55).run(&[0xF418]);
+    assert_eq!(chip8.sound_timer(), 0x55);
+}
+
+#[test]
+fn add_to_index() {
+    let chip8 = Test::new().index(0xFFE).register(1, 3).run(&[0xF11E]);
+    assert_eq!(chip8.index_register(), 0x1001);
+    assert_eq!(chip8.register(0xF), 0);
+}
+
+#[test]
+fn load_font() {
+    let chip8 = Test::new().register(2, 0xA).run(&[0xF229]);
+    assert_eq!(chip8.index_register(), 0xA * 5);
+}
+
+#[test]
+fn store_bcd() {
+    let cases = [
+        (0, [0, 0, 0]),
+        (9, [0, 0, 9]),
+        (10, [0, 1, 0]),
+        (99, [0, 9, 9]),
+        (100, [1, 0, 0]),
+        (137, [1, 3, 7]),
+        (255, [2, 5, 5]),
+    ];
+    for (value, digits) in cases {
+        let chip8 = Test::new().index(0x300).register(6, value).run(&[0xF633]);
+        assert_eq!(&chip8.memory()[0x300..0x303], &digits, "BCD of {}", value);
+    }
+
+    // Every value round-trips through the float arithmetic
+    for value in 0..=255u8 {
+        let chip8 = Test::new().index(0x300).register(0, value).run(&[0xF033]);
+        let digits = &chip8.memory()[0x300..0x303];
+        assert_eq!(digits[0] as u32 * 100 + digits[1] as u32 * 10 + digits[2] as u32, value as u32);
+    }
+}
+
+#[test]
+fn store_and_load_registers() {
+    let registers = Test::new().register(0, 1).register(1, 2).register(2, 3).register(3, 4);
+    let chip8 = registers.index(0x300).run(&[0xF255]);
+    assert_eq!(&chip8.memory()[0x300..0x304], &[1, 2, 3, 0]);
+    assert_eq!(chip8.index_register(), 0x300);
+
+    let chip8 = Test::new().index(0x300).memory(0x300, &[9, 8, 7, 6]).run(&[0xF265]);
+    assert_eq!([chip8.register(0), chip8.register(1), chip8.register(2), chip8.register(3)], [9, 8, 7, 0]);
+    assert_eq!(chip8.index_register(), 0x300);
+}
+
+#[test]
+fn store_and_load_increment_index_quirk() {
+    let quirks = Quirks { load_store_increments_i: true, ..Quirks::default() };
+    assert_eq!(Test::new().quirks(quirks).index(0x300).run(&[0xF255]).index_register(), 0x303);
+    assert_eq!(Test::new().quirks(quirks).index(0x300).run(&[0xF265]).index_register(), 0x303);
+}
+
+#[test]
+fn quirk_presets() {
+    assert_eq!(Platform::Chip8.default_quirks(), Quirks::default());
+    assert_eq!(Platform::SuperChip.default_quirks(), Quirks::super_chip());
+    assert_eq!(Platform::XoChip.default_quirks(), Quirks::xo_chip());
+
+    // The chosen quirks stay in place across a reset
+    let mut chip8 = Chip8::with_quirks(Quirks::cosmac_vip());
+    chip8.reset();
+    assert_eq!((chip8.platform(), chip8.quirks()), (Platform::Chip8, Quirks::cosmac_vip()));
+
+    // The VIP shifts VY, clears VF after logic and advances I on stores
+    let vip = || Test::new().quirks(Quirks::cosmac_vip()).register(0, 0xFF).register(1, 0b0110).register(0xF, 9);
+    assert_eq!(vip().run(&[0x8016]).register(0), 0b0011);
+    assert_eq!(vip().run(&[0x8011]).register(0xF), 0);
+    assert_eq!(vip().index(0x300).run(&[0xF155]).index_register(), 0x302);
+}
+
+#[test]
+fn store_past_the_end_of_memory_fails() {
+    let mut chip8 = Test::new().index(0xFFE).build(&[0xF255]);
+    assert!(matches!(chip8.cycle(), Err(Chip8Error::MemoryOutOfBounds { .. })));
+}
+
+#[test]
+fn invalid_opcode_fails() {
+    let mut chip8 = Test::new().build(&[0x5121]);
+    assert_eq!(chip8.cycle(), Err(Chip8Error::InvalidOpcode { pc: STARTING_ADDRESS, opcode: 0x5121 }));
+}
+
+#[test]
+fn later_platform_opcodes_fail_on_chip8() {
+    for opcode in [0x00FF, 0x00C1, 0xF075, 0xF000, 0x5122] {
+        let mut chip8 = Test::new().build(&[opcode]);
+        assert!(matches!(chip8.cycle(), Err(Chip8Error::InvalidOpcode { .. })), "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn exit_halts() {
+    let chip8 = Test::new().platform(Platform::SuperChip).run(&[0x00FD]);
+    assert!(chip8.is_halted());
+}
+
+#[test]
+fn high_resolution_and_big_sprites() {
+    let chip8 = Test::new().platform(Platform::SuperChip).run(&[0x00FF]);
+    let framebuffer = chip8.get_framebuffer();
+    assert_eq!((framebuffer.width, framebuffer.height), (128, 64));
+
+    // DXY0 draws 16 rows of two bytes
+    let sprite = [0xFF; 32];
+    let chip8 = Test::new()
+        .platform(Platform::SuperChip)
+        .index(0x300)
+        .memory(0x300, &sprite)
+        .run_cycles(&[0x00FF, 0xD000], 2);
+    assert_eq!(lit_pixels(&chip8), 256);
+    assert_eq!(pixel(&chip8, 15, 15), 1);
+    assert_eq!(pixel(&chip8, 16, 0), 0);
+}
+
+#[test]
+fn scroll() {
+    let schip = || Test::new().platform(Platform::SuperChip);
+    let chip8 = schip().run_cycles(&[0xD001, 0x00C2], 2);
+    assert_eq!(pixel(&chip8, 0, 0), 0);
+    assert_eq!(pixel(&chip8, 0, 2), 1);
+
+    let chip8 = schip().run_cycles(&[0xD001, 0x00FB], 2);
+    assert_eq!(pixel(&chip8, 0, 0), 0);
+    assert_eq!(pixel(&chip8, 4, 0), 1);
+
+    let chip8 = schip().register(0, 8).run_cycles(&[0xD011, 0x00FC], 2);
+    assert_eq!(pixel(&chip8, 8, 0), 0);
+    assert_eq!(pixel(&chip8, 4, 0), 1);
+}
+
+#[test]
+fn resolution_switches_clear_the_screen() {
+    // A single pixel sprite, drawn at X=100
+    let schip = || Test::new().platform(Platform::SuperChip).register(0, 100).index(0x300).memory(0x300, &[0x80]);
+    let chip8 = schip().run_cycles(&[0xD111, 0x00FF], 2);
+    assert_eq!(lit_pixels(&chip8), 0);
+
+    // X=100 only fits on the high resolution screen, and wraps to 36 on the low one
+    let chip8 = schip().run_cycles(&[0x00FF, 0xD011], 2);
+    assert_eq!(pixel(&chip8, 100, 0), 1);
+    let chip8 = schip().run_cycles(&[0x00FF, 0xD011, 0x00FE, 0xD011], 4);
+    let framebuffer = chip8.get_framebuffer();
+    assert_eq!((framebuffer.width, framebuffer.height), (64, 32));
+    assert_eq!(lit_pixels(&chip8), 1);
+    assert_eq!(pixel(&chip8, 36, 0), 1);
+}
+
+#[test]
+fn scrolled_out_pixels_are_lost() {
+    // Scrolling moves a single pixel off the edge instead of wrapping it around
+    let schip = || Test::new().platform(Platform::SuperChip).index(0x300).memory(0x300, &[0x80]);
+    let chip8 = schip().register(0, 30).run_cycles(&[0xD101, 0x00C4], 2);
+    assert_eq!(lit_pixels(&chip8), 0);
+    let chip8 = schip().register(0, 62).run_cycles(&[0xD011, 0x00FB], 2);
+    assert_eq!(lit_pixels(&chip8), 0);
+    let chip8 = schip().register(0, 2).run_cycles(&[0xD011, 0x00FC], 2);
+    assert_eq!(lit_pixels(&chip8), 0);
+}
+
+#[test]
+fn flags_round_trip() {
+    let mut chip8 = Test::new()
+        .platform(Platform::SuperChip)
+        .register(0, 0x11)
+        .register(1, 0x22)
+        .build(&[0xF175, 0x6000, 0x6100, 0xF185]);
+    for _ in 0..4 {
+        chip8.cycle().unwrap();
+    }
+    assert_eq!([chip8.register(0), chip8.register(1)], [0x11, 0x22]);
+}
+
+#[test]
+fn big_font() {
+    // The big glyphs are ten rows each and follow the small font
+    let chip8 = Test::new().platform(Platform::SuperChip).register(2, 0x13).run(&[0xF230]);
+    assert_eq!(chip8.index_register(), 80 + 3 * 10);
+    assert_eq!(&chip8.memory()[110..120], &[0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF]);
+
+    let chip8 = Test::new().platform(Platform::SuperChip).register(2, 1).run_cycles(&[0xF230, 0xD01A], 2);
+    assert_eq!(lit_pixels(&chip8), 2 + 4 + 4 + 2 * 5 + 8 * 2);
+}
+
+/// Flag storage that outlives the machines using it
+#[derive(Clone, Default)]
+struct SharedFlags(Rc<RefCell<MemoryFlagStorage>>);
+
+impl FlagStorage for SharedFlags {
+    fn load_flags(&mut self, rom_hash: u64) -> Option<[u8; RPL_FLAG_COUNT]> {
+        self.0.borrow_mut().load_flags(rom_hash)
+    }
+
+    fn save_flags(&mut self, rom_hash: u64, flags: &[u8; RPL_FLAG_COUNT]) {
+        self.0.borrow_mut().save_flags(rom_hash, flags);
+    }
+}
+
+#[test]
+fn flags_persist_per_rom() {
+    // V0 = NN, save V0 to the flags, then load it back at 0x206
+    let storage = SharedFlags::default();
+    let machine = |value: u16| {
+        let mut chip8 = Test::new().platform(Platform::SuperChip).build(&[0x6000 | value, 0xF075, 0x6000, 0xF085]);
+        chip8.set_flag_storage(Box::new(storage.clone()));
+        chip8
+    };
+
+    let mut first = machine(0x42);
+    first.cycle().unwrap();
+    first.cycle().unwrap();
+
+    // A fresh machine running the same ROM reads the saved flags
+    let mut second = machine(0x42);
+    second.set_program_counter(STARTING_ADDRESS + 6);
+    second.cycle().unwrap();
+    assert_eq!(second.register(0), 0x42);
+
+    // Another ROM keeps its own flags
+    let mut other = machine(0x43);
+    other.set_program_counter(STARTING_ADDRESS + 6);
+    other.cycle().unwrap();
+    assert_eq!(other.register(0), 0);
+}
+
+#[test]
+fn long_index_load() {
+    let chip8 = Test::new().platform(Platform::XoChip).run(&[0xF000, 0xBEEF]);
+    assert_eq!(chip8.index_register(), 0xBEEF);
+    assert_eq!(chip8.program_counter(), SKIPPED);
+}
+
+#[test]
+fn xo_chip_addresses_64k() {
+    assert_eq!(Test::new().build(&[]).memory().len(), 0x1000);
+    assert_eq!(Test::new().platform(Platform::XoChip).build(&[]).memory().len(), 0x10000);
+
+    // Loads and stores reach past the first 4 KiB
+    let xo = || Test::new().platform(Platform::XoChip);
+    let chip8 = xo().memory(0xBEEF, &[5, 6]).run_cycles(&[0xF000, 0xBEEF, 0xF165], 2);
+    assert_eq!([chip8.register(0), chip8.register(1)], [5, 6]);
+    let chip8 = xo().register(0, 9).index(0xFFFF).run(&[0xF055]);
+    assert_eq!(chip8.memory()[0xFFFF], 9);
+    let mut chip8 = xo().index(0xFFFF).build(&[0xF155]);
+    assert_eq!(chip8.cycle(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x10000 }));
+}
+
+#[test]
+fn skip_steps_over_a_long_index_load() {
+    let chip8 = Test::new().platform(Platform::XoChip).run(&[0x3000, 0xF000, 0xBEEF]);
+    assert_eq!(chip8.program_counter(), STARTING_ADDRESS + 6);
+
+    // Before XO-CHIP F000 is a single word
+    let chip8 = Test::new().platform(Platform::SuperChip).run(&[0x3000, 0xF000, 0xBEEF]);
+    assert_eq!(chip8.program_counter(), SKIPPED);
+}
+
+#[test]
+fn save_and_load_register_ranges() {
+    let xo = || Test::new().platform(Platform::XoChip).index(0x300);
+    let chip8 = xo().register(2, 1).register(3, 2).register(4, 3).run(&[0x5242]);
+    assert_eq!(&chip8.memory()[0x300..0x303], &[1, 2, 3]);
+
+    // A descending range stores the registers in reverse
+    let chip8 = xo().register(2, 1).register(3, 2).register(4, 3).run(&[0x5422]);
+    assert_eq!(&chip8.memory()[0x300..0x303], &[3, 2, 1]);
+    assert_eq!(chip8.index_register(), 0x300);
+
+    let chip8 = xo().memory(0x300, &[7, 8]).run(&[0x5673]);
+    assert_eq!([chip8.register(6), chip8.register(7)], [7, 8]);
+}
+
+#[test]
+fn plane_selection() {
+    let xo = || Test::new().platform(Platform::XoChip);
+
+    // Plane 2 alone draws colour 2
+    let chip8 = xo().run_cycles(&[0xF201, 0xD001], 2);
+    assert_eq!(pixel(&chip8, 0, 0), 2);
+
+    // Both planes read consecutive copies of the sprite data
+    let chip8 = xo().index(0x300).memory(0x300, &[0x80, 0xC0]).run_cycles(&[0xF301, 0xD001], 2);
+    assert_eq!(pixel(&chip8, 0, 0), 3);
+    assert_eq!(pixel(&chip8, 1, 0), 2);
+
+    // Clearing only affects the selected planes
+    let chip8 = xo().run_cycles(&[0xF301, 0xD001, 0xF101, 0x00E0], 4);
+    assert_eq!(pixel(&chip8, 0, 0), 2);
+
+    // There are only two planes, so a larger mask is not an instruction
+    let mut chip8 = xo().build(&[0xF401]);
+    assert_eq!(chip8.cycle(), Err(Chip8Error::InvalidOpcode { pc: STARTING_ADDRESS, opcode: 0xF401 }));
+}
+
+#[test]
+fn planes_scroll_and_collide_separately() {
+    let xo = || Test::new().platform(Platform::XoChip).index(0x300).memory(0x300, &[0x80, 0x80]);
+
+    // Scrolling with only plane 2 selected leaves plane 1 where it was
+    let chip8 = xo().run_cycles(&[0xF301, 0xD001, 0xF201, 0x00C1], 4);
+    assert_eq!([pixel(&chip8, 0, 0), pixel(&chip8, 0, 1)], [1, 2]);
+
+    // A collision is only reported for a plane that already had the pixel lit
+    let chip8 = xo().run_cycles(&[0xF101, 0xD001, 0xF201, 0xD001], 4);
+    assert_eq!((pixel(&chip8, 0, 0), chip8.register(0xF)), (3, 0));
+    let chip8 = xo().run_cycles(&[0xF101, 0xD001, 0xF301, 0xD001], 4);
+    assert_eq!((pixel(&chip8, 0, 0), chip8.register(0xF)), (2, 1));
+
+    // With no plane selected nothing is drawn
+    let chip8 = xo().run_cycles(&[0xF001, 0xD001], 2);
+    assert_eq!(lit_pixels(&chip8), 0);
+}
+
+#[test]
+fn audio_pattern() {
+    // Until F002 runs the frontend falls back to the buzzer tone
+    let chip8 = Test::new().platform(Platform::XoChip).build(&[0xF002]);
+    assert!(!chip8.has_audio_pattern());
+
+    let chip8 = Test::new().platform(Platform::XoChip).index(0x300).memory(0x300, &[0xF0; 16]).run(&[0xF002]);
+    assert!(chip8.has_audio_pattern());
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use chip8_core::{Chip8, Platform, RewindBuffer};
+use std::time::Duration;
+
+fn machine(platform: Platform) -> Chip8 {
+    common::brix(platform, 3)
+}
+
+fn run_frame(chip8: &mut Chip8) {
+    common::run_frames(chip8, 1);
+}
+
+/// A buffer holding `snapshots` snapshots taken every `interval` frames
+fn buffer(interval: u32, snapshots: u64) -> RewindBuffer {
+    RewindBuffer::new(interval, Duration::from_secs_f64((interval as u64 * snapshots) as f64 / 60.0))
+}
+
+/// Runs and records `frames` frames, returning the state after each one
+fn record(chip8: &mut Chip8, rewind: &mut RewindBuffer, frames: usize) -> Vec<Vec<u8>> {
+    (0..frames)
+        .map(|_| {
+            run_frame(chip8);
+            rewind.record_frame(chip8);
+            chip8.save_state()
+        })
+        .collect()
+}
+
+#[test]
+fn rewinds_through_every_snapshot() {
+    let mut chip8 = machine(Platform::Chip8);
+    let mut rewind = buffer(1, 100);
+    let states = record(&mut chip8, &mut rewind, 30);
+    assert_eq!(rewind.len(), 30);
+
+    for state in states.iter().rev() {
+        assert!(rewind.rewind(&mut chip8));
+        assert_eq!(&chip8.save_state(), state);
+    }
+}
+
+#[test]
+fn keeps_only_the_newest_snapshots() {
+    let mut chip8 = machine(Platform::Chip8);
+    let mut rewind = buffer(1, 5);
+    let states = record(&mut chip8, &mut rewind, 23);
+    assert_eq!(rewind.len(), 5);
+
+    for state in states[18..].iter().rev() {
+        assert!(rewind.rewind(&mut chip8));
+        assert_eq!(&chip8.save_state(), state);
+    }
+
+    // Past the oldest snapshot the oldest is restored again
+    for _ in 0..3 {
+        assert!(rewind.rewind(&mut chip8));
+        assert_eq!(chip8.save_state(), states[18]);
+    }
+    assert_eq!(rewind.len(), 1);
+}
+
+#[test]
+fn snapshots_follow_the_interval() {
+    let mut chip8 = machine(Platform::Chip8);
+    let mut rewind = buffer(3, 100);
+    let states = record(&mut chip8, &mut rewind, 10);
+
+    // Snapshots are taken on the first recorded frame and every third after it
+    for frame in [9, 6, 3, 0] {
+        assert!(rewind.rewind(&mut chip8));
+        assert_eq!(chip8.save_state(), states[frame]);
+    }
+}
+
+#[test]
+fn recording_resumes_after_a_rewind() {
+    let mut chip8 = machine(Platform::Chip8);
+    let mut rewind = buffer(1, 100);
+    let states = record(&mut chip8, &mut rewind, 10);
+    for _ in 0..4 {
+        rewind.rewind(&mut chip8);
+    }
+    assert_eq!(chip8.save_state(), states[6]);
+
+    // Play continues from the rewound-to state, recording on top of the older snapshots
+    let newer = record(&mut chip8, &mut rewind, 5);
+    for state in newer.iter().rev() {
+        assert!(rewind.rewind(&mut chip8));
+        assert_eq!(&chip8.save_state(), state);
+    }
+    assert!(rewind.rewind(&mut chip8));
+    assert_eq!(chip8.save_state(), states[5]);
+}
+
+#[test]
+fn states_of_different_sizes() {
+    // Loading an XO-CHIP state changes the memory size, so that delta is a full copy
+    let mut chip8 = machine(Platform::Chip8);
+    let mut xo_chip = machine(Platform::XoChip);
+    let mut rewind = buffer(1, 100);
+    let mut states = record(&mut chip8, &mut rewind, 3);
+    run_frame(&mut xo_chip);
+    chip8.load_state(&xo_chip.save_state()).unwrap();
+    states.extend(record(&mut chip8, &mut rewind, 3));
+
+    for state in states.iter().rev() {
+        assert!(rewind.rewind(&mut chip8));
+        assert_eq!(&chip8.save_state(), state);
+    }
+}
+
+#[test]
+fn nothing_to_rewind() {
+    let mut chip8 = machine(Platform::Chip8);
+    let before = chip8.save_state();
+    let mut rewind = buffer(1, 10);
+    assert!(rewind.is_empty());
+    assert!(!rewind.rewind(&mut chip8));
+    assert_eq!(chip8.save_state(), before);
+
+    record(&mut chip8, &mut rewind, 2);
+    rewind.clear();
+    assert!(!rewind.rewind(&mut chip8));
+}
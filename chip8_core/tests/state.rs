@@ -0,0 +1,127 @@
+mod common;
+
+use chip8_core::{crc32, Chip8, Platform, StateError, STATE_VERSION};
+use common::run_frames;
+
+/// Offset of the payload length in the header
+const LENGTH_OFFSET: usize = 8;
+/// Offset of the payload checksum in the header
+const CHECKSUM_OFFSET: usize = 12;
+const HEADER_SIZE: usize = 16;
+
+/// A machine part way through BRIX, so every part of the state holds something
+fn running_machine(platform: Platform) -> Chip8 {
+    let mut chip8 = common::brix(platform, 7);
+    run_frames(&mut chip8, 30);
+    chip8
+}
+
+/// Replaces the payload of a state, fixing up its length and checksum
+fn with_payload(state: &[u8], edit: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+    let mut payload = state[HEADER_SIZE..].to_vec();
+    edit(&mut payload);
+    let mut edited = state[..HEADER_SIZE].to_vec();
+    edited[LENGTH_OFFSET..LENGTH_OFFSET + 4].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    edited[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].copy_from_slice(&crc32(&payload).to_le_bytes());
+    edited.extend_from_slice(&payload);
+    edited
+}
+
+/// Loads a bad state and checks the machine kept its own state
+fn assert_rejected(state: &[u8], expected: StateError) {
+    let mut chip8 = running_machine(Platform::Chip8);
+    let before = chip8.save_state();
+    assert_eq!(chip8.load_state(state), Err(expected));
+    assert_eq!(chip8.save_state(), before);
+}
+
+#[test]
+fn round_trip() {
+    for platform in [Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+        let mut chip8 = running_machine(platform);
+        let state = chip8.save_state();
+        let mut original = running_machine(platform);
+
+        run_frames(&mut chip8, 20);
+        assert_ne!(chip8.save_state(), state);
+        chip8.load_state(&state).unwrap();
+        assert_eq!(chip8.save_state(), state);
+
+        // Both machines carry on identically, random numbers included
+        run_frames(&mut chip8, 20);
+        run_frames(&mut original, 20);
+        assert_eq!(chip8.save_state(), original.save_state());
+    }
+}
+
+#[test]
+fn loading_switches_platform() {
+    let state = running_machine(Platform::XoChip).save_state();
+    let mut chip8 = Chip8::new(Platform::Chip8, Platform::Chip8.default_quirks());
+    chip8.load_state(&state).unwrap();
+    assert_eq!(chip8.platform(), Platform::XoChip);
+    assert_eq!(chip8.memory().len(), 0x10000);
+}
+
+#[test]
+fn flipped_byte_fails_checksum() {
+    let mut state = running_machine(Platform::Chip8).save_state();
+    let last = state.len() - 1;
+    state[last] ^= 0x01;
+    let error = running_machine(Platform::Chip8).load_state(&state).unwrap_err();
+    assert!(matches!(error, StateError::ChecksumMismatch { .. }));
+    assert_rejected(&state, error);
+}
+
+#[test]
+fn bad_magic() {
+    let mut state = running_machine(Platform::Chip8).save_state();
+    state[0] = b'X';
+    assert_rejected(&state, StateError::BadMagic);
+}
+
+#[test]
+fn unsupported_versions() {
+    let mut state = running_machine(Platform::Chip8).save_state();
+    state[4..6].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+    assert_rejected(&state, StateError::UnsupportedVersion { version: STATE_VERSION + 1 });
+    state[4..6].copy_from_slice(&0u16.to_le_bytes());
+    assert_rejected(&state, StateError::UnsupportedVersion { version: 0 });
+    assert_eq!(StateError::UnsupportedVersion { version: 0 }.to_string(), "unsupported save state version 0");
+}
+
+#[test]
+fn truncated() {
+    let state = running_machine(Platform::Chip8).save_state();
+    assert_rejected(&[], StateError::Truncated);
+    assert_rejected(&state[..10], StateError::Truncated);
+    assert_rejected(&state[..state.len() - 1], StateError::Truncated);
+
+    // A payload cut short with a matching header still fails
+    let short = with_payload(&state, |payload| payload.truncate(payload.len() - 1));
+    assert_rejected(&short, StateError::Truncated);
+}
+
+#[test]
+fn trailing_bytes() {
+    let state = running_machine(Platform::Chip8).save_state();
+    let mut longer = state.clone();
+    longer.push(0);
+    assert_rejected(&longer, StateError::InvalidField { field: "state length" });
+
+    let longer_payload = with_payload(&state, |payload| payload.push(0));
+    assert_rejected(&longer_payload, StateError::InvalidField { field: "payload length" });
+}
+
+#[test]
+fn invalid_fields() {
+    let state = running_machine(Platform::Chip8).save_state();
+    let bad_platform = with_payload(&state, |payload| payload[0] = 7);
+    assert_rejected(&bad_platform, StateError::InvalidField { field: "platform" });
+    let bad_quirks = with_payload(&state, |payload| payload[1] |= 0x40);
+    assert_rejected(&bad_quirks, StateError::InvalidField { field: "quirks" });
+
+    // The stack pointer follows the platform, quirks, PC and I
+    let bad_stack = with_payload(&state, |payload| payload[6..8].copy_from_slice(&100u16.to_le_bytes()));
+    assert_rejected(&bad_stack, StateError::InvalidField { field: "stack pointer" });
+}
@@ -0,0 +1,99 @@
+use chip8_core::{Chip8, Platform, Tracer};
+use std::fs;
+use std::process::Command;
+
+/// Loads 5 into V0, adds 1, points I at 0x300 and spins
+const PROGRAM: [u16; 4] = [0x6005, 0x7001, 0xA300, 0x1206];
+
+/// Runs `frames` frames of two cycles each under `tracer`, returning the trace
+fn trace(mut tracer: Tracer<Vec<u8>>, frames: u64) -> Vec<String> {
+    let bytes: Vec<u8> = PROGRAM.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+    let mut chip8 = Chip8::new(Platform::Chip8, Platform::Chip8.default_quirks());
+    chip8.load_program(&bytes).unwrap();
+    chip8.set_delay_timer(3);
+    for _ in 0..frames {
+        for _ in 0..2 {
+            tracer.trace(&chip8).unwrap();
+            chip8.cycle().unwrap();
+        }
+        chip8.update_timers();
+        tracer.end_frame();
+    }
+    assert_eq!((tracer.cycles(), tracer.frame()), (frames * 2, frames));
+    let text = String::from_utf8(tracer.into_inner().unwrap()).unwrap();
+    text.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn line_format() {
+    let zeros = " 00".repeat(15);
+    assert_eq!(
+        trace(Tracer::new(Vec::new()), 3),
+        [
+            format!("         0 0200 6005 LD V0, 0x05          | 00{} | I=0000 SP=00 DT=03 ST=00", zeros),
+            format!("         1 0202 7001 ADD V0, 0x01         | 05{} | I=0000 SP=00 DT=03 ST=00", zeros),
+            format!("         2 0204 A300 LD I, 0x300          | 06{} | I=0000 SP=00 DT=02 ST=00", zeros),
+            format!("         3 0206 1206 JP 0x206             | 06{} | I=0300 SP=00 DT=02 ST=00", zeros),
+            format!("         4 0206 1206 JP 0x206             | 06{} | I=0300 SP=00 DT=01 ST=00", zeros),
+            format!("         5 0206 1206 JP 0x206             | 06{} | I=0300 SP=00 DT=01 ST=00", zeros),
+        ]
+    );
+}
+
+#[test]
+fn filters() {
+    let pcs = |lines: &[String]| -> Vec<String> { lines.iter().map(|line| line[11..15].to_string()).collect() };
+    let cycles = |lines: &[String]| -> Vec<u64> { lines.iter().map(|line| line[..10].trim().parse().unwrap()).collect() };
+
+    let mut tracer = Tracer::new(Vec::new());
+    tracer.set_address_range(0x202..=0x204);
+    assert_eq!(pcs(&trace(tracer, 3)), ["0202", "0204"]);
+
+    // Frames count from 0 and the range excludes its end
+    let mut tracer = Tracer::new(Vec::new());
+    tracer.set_frames(1..2);
+    assert_eq!(cycles(&trace(tracer, 3)), [2, 3]);
+
+    let mut tracer = Tracer::new(Vec::new());
+    tracer.set_address_range(0x206..=0x206);
+    tracer.set_frames(2..10);
+    assert_eq!(cycles(&trace(tracer, 3)), [4, 5]);
+}
+
+#[test]
+fn tracediff_reports_the_first_divergence() {
+    let lines = trace(Tracer::new(Vec::new()), 3);
+    let mut changed = lines.clone();
+    changed[2] = changed[2].replacen("| 06 00", "| 07 00", 1);
+    changed[4] = changed[4].replacen("I=0300", "I=0301", 1);
+
+    let dir = std::env::temp_dir().join(format!("chip8-trace-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let write = |name: &str, lines: &[String]| {
+        let path = dir.join(name);
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+        path
+    };
+    let (a, b, short) = (write("a.trace", &lines), write("b.trace", &changed), write("short.trace", &lines[..4]));
+    let tracediff = |args: &[&std::path::Path]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_chip8-tracediff")).args(args).output().unwrap();
+        (output.status.code(), String::from_utf8(output.stdout).unwrap())
+    };
+
+    let (status, stdout) = tracediff(&[&a, &a]);
+    assert_eq!((status, stdout.as_str()), (Some(0), "Traces match (6 lines)\n"));
+
+    let (status, stdout) = tracediff(&[&a, &b]);
+    assert_eq!(status, Some(1));
+    let report: Vec<&str> = stdout.lines().collect();
+    assert_eq!(report[0], "Traces diverge at line 3 (cycle 2): V0 is 06 vs 07");
+    assert_eq!(report[1..3], [format!("  {}", lines[0]), format!("  {}", lines[1])]);
+    assert_eq!(report[3..], [format!("< {}", lines[2]), format!("> {}", changed[2])]);
+
+    let (status, stdout) = tracediff(&[&a, &short]);
+    assert_eq!(status, Some(1));
+    let expected = format!("{} ends at line 4, {} continues\n", short.display(), a.display());
+    assert_eq!(stdout, expected);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,124 @@
+use chip8_core::Chip8;
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+use std::f32::consts::TAU;
+
+const SAMPLE_RATE: i32 = 44100;
+const FRAME_RATE: i32 = 60;
+/// Frames of audio allowed to wait in the queue before new frames are dropped.
+const MAX_QUEUED_FRAMES: u32 = 4;
+
+/// Shape of the tone played for the classic sound timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    /// Parses a waveform name as given on the command line.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "triangle" => Some(Waveform::Triangle),
+            "sawtooth" => Some(Waveform::Sawtooth),
+            "sine" => Some(Waveform::Sine),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the wave at a phase between 0.0 and 1.0.
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Sine => (phase * TAU).sin(),
+        }
+    }
+}
+
+/// Settings for the buzzer tone.
+#[derive(Debug, Clone, Copy)]
+pub struct ToneSettings {
+    pub frequency: f32,
+    pub waveform: Waveform,
+    pub volume: f32,
+}
+
+impl Default for ToneSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            waveform: Waveform::Square,
+            volume: 0.25,
+        }
+    }
+}
+
+/// Streams the emulator's sound to an SDL audio device.
+///
+/// XO-CHIP programs play their own audio pattern, rendered by the core. Every
+/// other platform gets a plain tone while the sound timer is running.
+pub struct AudioOutput {
+    queue: AudioQueue<f32>,
+    buffer: Vec<f32>,
+    tone: ToneSettings,
+    /// Position within the current tone period, kept across frames so the wave never jumps.
+    phase: f32,
+}
+
+impl AudioOutput {
+    /// Opens the default playback device as a mono float stream.
+    pub fn open(subsystem: &AudioSubsystem, tone: ToneSettings) -> Result<Self, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+        let queue = subsystem.open_queue::<f32, _>(None, &desired)?;
+        let samples_per_frame = (queue.spec().freq / FRAME_RATE) as usize;
+        queue.resume();
+
+        Ok(Self {
+            queue,
+            buffer: vec![0.0; samples_per_frame],
+            tone,
+            phase: 0.0,
+        })
+    }
+
+    /// Renders one frame worth of audio from the emulator and queues it for playback.
+    pub fn play_frame(&mut self, chip8: &mut Chip8) {
+        let sample_rate = self.queue.spec().freq as u32;
+        if chip8.has_audio_pattern() {
+            chip8.render_audio(&mut self.buffer, sample_rate);
+        } else if chip8.is_buzzer_active() {
+            self.render_tone(sample_rate);
+        } else {
+            self.buffer.fill(0.0);
+        }
+        for sample in self.buffer.iter_mut() {
+            *sample *= self.tone.volume;
+        }
+
+        let frame_bytes = (self.buffer.len() * std::mem::size_of::<f32>()) as u32;
+        if self.queue.size() > frame_bytes * MAX_QUEUED_FRAMES {
+            return;
+        }
+        if let Err(err) = self.queue.queue_audio(&self.buffer) {
+            eprintln!("Warning: Could not queue audio. {}", err);
+        }
+    }
+
+    /// Fills the buffer with the buzzer tone, continuing from the previous frame's phase.
+    fn render_tone(&mut self, sample_rate: u32) {
+        let step = self.tone.frequency / sample_rate as f32;
+        for sample in self.buffer.iter_mut() {
+            *sample = self.tone.waveform.sample(self.phase);
+            self.phase = (self.phase + step).fract();
+        }
+    }
+}
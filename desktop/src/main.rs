@@ -1,46 +1,147 @@
+mod audio;
+mod slots;
+mod storage;
+
+use audio::{AudioOutput, ToneSettings, Waveform};
 use chip8_core::*;
-use chip8_core::{MEMORY_SIZE, STARTING_ADDRESS};
 extern crate sdl2;
 use std::env;
 use std::fs::File;
-use std::io::Read;
+use std::io::{LineWriter, Read};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod, Scancode};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 
-const SCALE: u32 = 15;
+const SCALE: u32 = 16;
 const WINDOW_WIDTH: u32 = (DISPLAY_WIDTH as u32) * SCALE;
 const WINDOW_HEIGHT: u32 = (DISPLAY_HEIGHT as u32) * SCALE;
 const CYCLES_PER_FRAME: usize = 10;
+const REWIND_KEY: Scancode = Scancode::Backspace;
+const DEFAULT_PALETTE: [Color; 4] = [
+    Color::RGB(0, 0, 0),
+    Color::RGB(255, 255, 255),
+    Color::RGB(170, 170, 170),
+    Color::RGB(85, 85, 85),
+];
+
+/// Settings chosen on the command line.
+struct Options {
+    game_path: String,
+    platform: Platform,
+    palette: [Color; 4],
+    tone: ToneSettings,
+    seed: Option<u64>,
+    rewind_seconds: u64,
+    rewind_interval: u32,
+    trace_path: Option<String>,
+}
 
 fn main() {
     // Parse command-line arguments
-    let game_path = parse_arguments();
+    let options = parse_arguments();
 
     // Initialize SDL and Chip-8 Emulator
-    let (mut canvas, mut event_pump) = initialize_sdl();
-    let mut chip8 = initialize_chip8(&game_path);
+    let (mut canvas, mut event_pump, mut audio) = initialize_sdl(&options);
+    let mut chip8 = initialize_chip8(&options);
 
     // Main game loop
-    run_emulator(&mut chip8, &mut canvas, &mut event_pump);
+    run_emulator(&mut chip8, &mut canvas, &mut event_pump, audio.as_mut(), &options);
 }
 
-/// Parses the command-line arguments and returns the path to the game file.
-fn parse_arguments() -> String {
+/// Parses the command-line arguments into the emulator options.
+fn parse_arguments() -> Options {
     let mut args = env::args();
     let program_name = args.next().unwrap_or_else(|| "chip8_emulator".to_string());
-
-    args.next().unwrap_or_else(|| {
-        eprintln!("Usage: {} <path_to_game>", program_name);
+    let usage = || -> ! {
+        eprintln!(
+            "Usage: {} [--platform chip8|schip|xochip] [--palette RRGGBB,RRGGBB,RRGGBB,RRGGBB] \
+             [--tone-frequency HZ] [--waveform square|triangle|sawtooth|sine] [--volume 0.0-1.0] \
+             [--seed N] [--rewind-seconds N] [--rewind-interval FRAMES] [--trace FILE] <path_to_game>",
+            program_name
+        );
         std::process::exit(1);
-    })
+    };
+
+    let mut game_path = None;
+    let mut platform = Platform::Chip8;
+    let mut palette = DEFAULT_PALETTE;
+    let mut tone = ToneSettings::default();
+    let mut seed = None;
+    let mut rewind_seconds = 60;
+    let mut rewind_interval = 2;
+    let mut trace_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => platform = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--palette" => {
+                palette = args.next().as_deref().and_then(parse_palette).unwrap_or_else(|| usage());
+            }
+            "--tone-frequency" => {
+                tone.frequency = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&hz: &f32| hz > 0.0)
+                    .unwrap_or_else(|| usage());
+            }
+            "--waveform" => {
+                tone.waveform = args.next().as_deref().and_then(Waveform::from_name).unwrap_or_else(|| usage());
+            }
+            "--volume" => {
+                tone.volume = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|volume| (0.0..=1.0).contains(volume))
+                    .unwrap_or_else(|| usage());
+            }
+            "--seed" => {
+                seed = Some(args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()));
+            }
+            "--rewind-seconds" => {
+                rewind_seconds = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage());
+            }
+            "--rewind-interval" => {
+                rewind_interval = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&frames| frames > 0)
+                    .unwrap_or_else(|| usage());
+            }
+            "--trace" => trace_path = Some(args.next().unwrap_or_else(|| usage())),
+            _ if game_path.is_none() => game_path = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    Options {
+        game_path: game_path.unwrap_or_else(|| usage()),
+        platform,
+        palette,
+        tone,
+        seed,
+        rewind_seconds,
+        rewind_interval,
+        trace_path,
+    }
+}
+
+/// Parses four comma-separated hex colours, one per 2-bit pixel value.
+fn parse_palette(text: &str) -> Option<[Color; 4]> {
+    let colors = text
+        .split(',')
+        .map(|hex| {
+            let rgb = u32::from_str_radix(hex.trim().trim_start_matches('#'), 16).ok()?;
+            Some(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+        })
+        .collect::<Option<Vec<Color>>>()?;
+    colors.try_into().ok()
 }
 
-/// Initializes SDL2 and returns the canvas and event pump.
-fn initialize_sdl() -> (Canvas<Window>, sdl2::EventPump) {
+/// Initializes SDL2 and returns the canvas, event pump and audio output, if any.
+fn initialize_sdl(options: &Options) -> (Canvas<Window>, sdl2::EventPump, Option<AudioOutput>) {
     // Step 1: Initialize SDL2
     let sdl_context = match sdl2::init() {
         Ok(context) => context,
@@ -91,23 +192,44 @@ fn initialize_sdl() -> (Canvas<Window>, sdl2::EventPump) {
         }
     };
 
-    // Step 6: Return the canvas and event pump
-    (canvas, event_pump)
+    // Step 6: Open the audio device, carrying on without sound if there is none
+    let audio = match sdl_context.audio().and_then(|subsystem| AudioOutput::open(&subsystem, options.tone)) {
+        Ok(output) => Some(output),
+        Err(err) => {
+            eprintln!("Warning: Failed to open SDL2 audio device, sound is disabled: {}", err);
+            None
+        }
+    };
+
+    // Step 7: Return the canvas, event pump and audio output
+    (canvas, event_pump, audio)
 }
 
 /// Initializes the Chip-8 emulator and loads the game.
 /// Initializes the Chip-8 emulator and loads the game program.
-fn initialize_chip8(game_path: &str) -> Chip8 {
-    let mut chip8 = create_chip8_instance();
-    let rom_data = read_game_file(game_path);
+fn initialize_chip8(options: &Options) -> Chip8 {
+    let mut chip8 = create_chip8_instance(options.platform);
+    chip8.set_flag_storage(Box::new(storage::FileFlagStorage::new()));
+    chip8.seed_rng(options.seed.unwrap_or_else(time_seed));
+    let rom_data = read_game_file(&options.game_path);
     load_rom_into_chip8(&mut chip8, &rom_data);
     chip8
 }
 
+/// Derives a seed from the clock so every run without `--seed` plays differently.
+fn time_seed() -> u64 {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(DEFAULT_SEED);
+    println!("Using random seed {} (pass --seed to replay this run).", seed);
+    seed
+}
+
 /// Creates and returns a new Chip-8 instance.
-fn create_chip8_instance() -> Chip8 {
-    println!("Initializing Chip-8 emulator...");
-    Chip8::initialize()
+fn create_chip8_instance(platform: Platform) -> Chip8 {
+    println!("Initializing Chip-8 emulator ({:?})...", platform);
+    Chip8::new(platform, platform.default_quirks())
 }
 
 /// Reads the game ROM from the specified file path.
@@ -133,37 +255,102 @@ fn read_game_file(game_path: &str) -> Vec<u8> {
 fn load_rom_into_chip8(chip8: &mut Chip8, rom_data: &[u8]) {
     println!("Loading ROM into Chip-8 memory...");
 
-    if rom_data.len() > (MEMORY_SIZE - STARTING_ADDRESS as usize) {
-        eprintln!(
-            "Error: ROM size ({}) exceeds available memory space ({} bytes).",
-            rom_data.len(),
-            MEMORY_SIZE - STARTING_ADDRESS as usize
-        );
+    let info = chip8.load_program(rom_data).unwrap_or_else(|err| {
+        eprintln!("Error: Could not load ROM. {}", err);
         std::process::exit(1);
-    }
+    });
 
-    chip8.load_program(rom_data);
-    println!("ROM successfully loaded into Chip-8 memory.");
+    println!(
+        "ROM successfully loaded into Chip-8 memory ({} bytes free).",
+        info.bytes_remaining
+    );
 }
 
 
 /// Runs the Chip-8 emulator loop.
-fn run_emulator(chip8: &mut Chip8, canvas: &mut Canvas<Window>, event_pump: &mut sdl2::EventPump) {
-    'game_loop: loop {
-        handle_events(chip8, event_pump);
+fn run_emulator(
+    chip8: &mut Chip8,
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut sdl2::EventPump,
+    mut audio: Option<&mut AudioOutput>,
+    options: &Options,
+) {
+    let mut rewind = RewindBuffer::new(options.rewind_interval, Duration::from_secs(options.rewind_seconds));
+    let mut tracer = options.trace_path.as_deref().map(open_trace);
+
+    loop {
+        handle_events(chip8, event_pump, options);
+
+        // Holding the rewind key steps back one snapshot per frame instead of running
+        if event_pump.keyboard_state().is_scancode_pressed(REWIND_KEY) {
+            if rewind.rewind(chip8) {
+                sync_keys(chip8, event_pump);
+            }
+            render_display(chip8, canvas, &options.palette);
+            continue;
+        }
+
         for _ in 0..CYCLES_PER_FRAME {
-            chip8.cycle();
+            if let Some(active) = tracer.as_mut() {
+                if let Err(err) = active.trace(chip8) {
+                    eprintln!("Error: Failed to write trace, tracing stopped: {}", err);
+                    tracer = None;
+                }
+            }
+            if let Err(err) = chip8.cycle() {
+                report_crash(&err);
+            }
+        }
+        if let Some(audio) = audio.as_deref_mut() {
+            audio.play_frame(chip8);
         }
         chip8.update_timers();
-        render_display(chip8, canvas);
+        if let Some(active) = tracer.as_mut() {
+            active.end_frame();
+        }
+        rewind.record_frame(chip8);
+        render_display(chip8, canvas, &options.palette);
+    }
+}
+
+/// Creates the trace file. Lines are written through as they complete, so the
+/// trace survives the process exiting on a crash or when the window closes.
+fn open_trace(path: &str) -> Tracer<LineWriter<File>> {
+    match File::create(path) {
+        Ok(file) => Tracer::new(LineWriter::new(file)),
+        Err(err) => {
+            eprintln!("Error: Failed to create {}: {}", path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Sets the Chip-8 keys to match the keyboard, since a restored state carries its own key state.
+fn sync_keys(chip8: &mut Chip8, event_pump: &sdl2::EventPump) {
+    for key_index in 0..16 {
+        chip8.set_key_state(key_index, false);
     }
+    for scancode in event_pump.keyboard_state().pressed_scancodes() {
+        if let Some(mapped_key) = Keycode::from_scancode(scancode).and_then(map_key_to_button) {
+            chip8.set_key_state(mapped_key, true);
+        }
+    }
+}
+
+/// Prints a crash report for a fatal emulation error and exits.
+fn report_crash(err: &Chip8Error) -> ! {
+    eprintln!("The Chip-8 program crashed: {}", err);
+    std::process::exit(1);
 }
 
 /// Handles user input events.
-fn handle_events(chip8: &mut Chip8, event_pump: &mut sdl2::EventPump) {
+fn handle_events(chip8: &mut Chip8, event_pump: &mut sdl2::EventPump, options: &Options) {
     for event in event_pump.poll_iter() {
         match event {
             Event::Quit { .. } => handle_quit_event(),
+            Event::KeyDown { keycode: Some(key), keymod, repeat: false, .. } if slots::slot_for_key(key).is_some() => {
+                handle_slot_key_event(chip8, key, keymod, options)
+            }
             Event::KeyDown { keycode: Some(key), .. } => handle_key_down_event(chip8, key),
             Event::KeyUp { keycode: Some(key), .. } => handle_key_up_event(chip8, key),
             _ => handle_other_event(event),
@@ -171,6 +358,25 @@ fn handle_events(chip8: &mut Chip8, event_pump: &mut sdl2::EventPump) {
     }
 }
 
+/// Saves to a slot on F1-F9, or loads from it when Shift is held.
+fn handle_slot_key_event(chip8: &mut Chip8, key: Keycode, keymod: Mod, options: &Options) {
+    let Some(slot) = slots::slot_for_key(key) else {
+        return;
+    };
+
+    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+        match slots::load_slot(chip8, slot) {
+            Ok(path) => println!("Loaded slot {} from '{}'.", slot, path.display()),
+            Err(err) => eprintln!("Error: Could not load slot {}. {}", slot, err),
+        }
+    } else {
+        match slots::save_slot(chip8, slot, &options.palette) {
+            Ok(path) => println!("Saved slot {} to '{}'.", slot, path.display()),
+            Err(err) => eprintln!("Error: Could not save slot {}. {}", slot, err),
+        }
+    }
+}
+
 /// Handles the quit event by exiting the program.
 fn handle_quit_event() {
     println!("Quit event received. Exiting the emulator...");
@@ -204,19 +410,20 @@ fn handle_other_event(event: Event) {
 
 
 /// Renders the Chip-8 framebuffer to the SDL canvas.
-fn render_display(chip8: &Chip8, canvas: &mut Canvas<Window>) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
+fn render_display(chip8: &Chip8, canvas: &mut Canvas<Window>, palette: &[Color; 4]) {
+    canvas.set_draw_color(palette[0]);
     canvas.clear();
 
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
     let framebuffer = chip8.get_framebuffer();
+    let scale = WINDOW_WIDTH / framebuffer.width as u32;
 
-    for (index, &pixel) in framebuffer.iter().enumerate() {
-        if pixel {
-            let x = (index % DISPLAY_WIDTH) as u32;
-            let y = (index / DISPLAY_WIDTH) as u32;
+    for (index, &pixel) in framebuffer.pixels.iter().enumerate() {
+        if pixel != 0 {
+            let x = (index % framebuffer.width) as u32;
+            let y = (index / framebuffer.width) as u32;
 
-            let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
+            canvas.set_draw_color(palette[pixel as usize & 0x3]);
+            let rect = Rect::new((x * scale) as i32, (y * scale) as i32, scale, scale);
             canvas.fill_rect(rect).unwrap();
         }
     }
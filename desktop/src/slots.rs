@@ -0,0 +1,64 @@
+use crate::storage::data_directory;
+use chip8_core::Chip8;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Maps F1-F9 to save slots 1-9.
+pub fn slot_for_key(key: Keycode) -> Option<u8> {
+    const SLOT_KEYS: [Keycode; 9] = [
+        Keycode::F1,
+        Keycode::F2,
+        Keycode::F3,
+        Keycode::F4,
+        Keycode::F5,
+        Keycode::F6,
+        Keycode::F7,
+        Keycode::F8,
+        Keycode::F9,
+    ];
+
+    SLOT_KEYS.iter().position(|&k| k == key).map(|index| index as u8 + 1)
+}
+
+/// Returns the folder holding the save slots of the loaded ROM.
+fn slot_directory(chip8: &Chip8) -> PathBuf {
+    data_directory()
+        .join("states")
+        .join(format!("{:016x}", chip8.rom_hash()))
+}
+
+/// Writes the machine state to a slot, along with a thumbnail of the display.
+pub fn save_slot(chip8: &Chip8, slot: u8, palette: &[Color; 4]) -> Result<PathBuf, Box<dyn Error>> {
+    let directory = slot_directory(chip8);
+    fs::create_dir_all(&directory)?;
+
+    let state_path = directory.join(format!("slot{}.state", slot));
+    fs::write(&state_path, chip8.save_state())?;
+    write_thumbnail(chip8, &directory.join(format!("slot{}.ppm", slot)), palette)?;
+    Ok(state_path)
+}
+
+/// Restores the machine state stored in a slot.
+pub fn load_slot(chip8: &mut Chip8, slot: u8) -> Result<PathBuf, Box<dyn Error>> {
+    let state_path = slot_directory(chip8).join(format!("slot{}.state", slot));
+    let state = fs::read(&state_path)?;
+    chip8.load_state(&state)?;
+    Ok(state_path)
+}
+
+/// Saves the framebuffer at its native resolution as a binary PPM image.
+fn write_thumbnail(chip8: &Chip8, path: &Path, palette: &[Color; 4]) -> Result<(), Box<dyn Error>> {
+    let framebuffer = chip8.get_framebuffer();
+    let mut image = Vec::with_capacity(framebuffer.pixels.len() * 3 + 16);
+    write!(image, "P6\n{} {}\n255\n", framebuffer.width, framebuffer.height)?;
+    for &pixel in framebuffer.pixels {
+        let color = palette[pixel as usize & 0x3];
+        image.extend_from_slice(&[color.r, color.g, color.b]);
+    }
+    fs::write(path, image)?;
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use chip8_core::{FlagStorage, RPL_FLAG_COUNT};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Returns the directory the emulator keeps its per-ROM data in.
+///
+/// `CHIP8_DATA_DIR` overrides the platform default.
+pub fn data_directory() -> PathBuf {
+    if let Some(dir) = env::var_os("CHIP8_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+    if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        return PathBuf::from(dir).join("chip8");
+    }
+    if let Some(dir) = env::var_os("APPDATA") {
+        return PathBuf::from(dir).join("chip8");
+    }
+    if let Some(home) = env::var_os("HOME") {
+        return PathBuf::from(home).join(".local").join("share").join("chip8");
+    }
+    PathBuf::from(".chip8")
+}
+
+/// Stores RPL user flags as one small file per ROM in the data directory.
+pub struct FileFlagStorage {
+    directory: PathBuf,
+}
+
+impl FileFlagStorage {
+    /// Creates a storage writing to the `flags` folder of the data directory.
+    pub fn new() -> Self {
+        Self { directory: data_directory().join("flags") }
+    }
+
+    fn path_for(&self, rom_hash: u64) -> PathBuf {
+        self.directory.join(format!("{:016x}.flags", rom_hash))
+    }
+}
+
+impl FlagStorage for FileFlagStorage {
+    fn load_flags(&mut self, rom_hash: u64) -> Option<[u8; RPL_FLAG_COUNT]> {
+        let data = fs::read(self.path_for(rom_hash)).ok()?;
+        data.try_into().ok()
+    }
+
+    fn save_flags(&mut self, rom_hash: u64, flags: &[u8; RPL_FLAG_COUNT]) {
+        let result = fs::create_dir_all(&self.directory)
+            .and_then(|_| fs::write(self.path_for(rom_hash), flags));
+        if let Err(err) = result {
+            eprintln!("Warning: Could not save RPL flags. {}", err);
+        }
+    }
+}
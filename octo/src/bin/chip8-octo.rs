@@ -0,0 +1,70 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+struct Options {
+    source_path: PathBuf,
+    output_path: Option<PathBuf>,
+    symbols_path: Option<PathBuf>,
+}
+
+fn parse_arguments() -> Options {
+    let mut args = env::args();
+    let program_name = args.next().unwrap_or_else(|| "chip8-octo".to_string());
+    let usage = || -> ! {
+        eprintln!("Usage: {} [-o <output.ch8>] [--symbols <output.sym>] <source.8o>", program_name);
+        std::process::exit(1);
+    };
+
+    let mut source_path = None;
+    let mut output_path = None;
+    let mut symbols_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output_path = Some(args.next().map(PathBuf::from).unwrap_or_else(|| usage())),
+            "--symbols" => symbols_path = Some(args.next().map(PathBuf::from).unwrap_or_else(|| usage())),
+            _ if arg.starts_with('-') => usage(),
+            _ => source_path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    Options {
+        source_path: source_path.unwrap_or_else(|| usage()),
+        output_path,
+        symbols_path,
+    }
+}
+
+fn write_file(path: &PathBuf, contents: &[u8]) {
+    if let Err(err) = fs::write(path, contents) {
+        eprintln!("Error: Failed to write {}: {}", path.display(), err);
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    let options = parse_arguments();
+    let source = match fs::read_to_string(&options.source_path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Error: Failed to read {}: {}", options.source_path.display(), err);
+            std::process::exit(1);
+        }
+    };
+    let program = match octo::compile(&source) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("Error: {}:{}", options.source_path.display(), err);
+            std::process::exit(1);
+        }
+    };
+
+    let output_path = options
+        .output_path
+        .unwrap_or_else(|| options.source_path.with_extension("ch8"));
+    write_file(&output_path, &program.bytes);
+    if let Some(symbols_path) = &options.symbols_path {
+        write_file(symbols_path, program.symbol_table().as_bytes());
+    }
+    println!("Compiled {} bytes to {}", program.bytes.len(), output_path.display());
+}
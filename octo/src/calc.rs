@@ -0,0 +1,113 @@
+use crate::tokens::{parse_number, Token};
+use crate::CompileError;
+
+const UNARY: [&str; 13] = ["-", "~", "!", "sin", "cos", "tan", "exp", "log", "abs", "sqrt", "sign", "ceil", "floor"];
+const BINARY: [&str; 19] = [
+    "-", "+", "*", "/", "%", "&", "|", "^", "<<", ">>", "pow", "min", "max", "<", "<=", "==", "!=", ">=", ">",
+];
+
+/// Evaluates the body of a `:calc` or `:byte { }` expression.
+///
+/// As in Octo, operators have no precedence: expressions are evaluated
+/// right to left, so `2 * 3 + 1` is 8. Use parentheses to group.
+pub(crate) fn evaluate(
+    tokens: &[Token],
+    open: &Token,
+    lookup: &dyn Fn(&str) -> Option<f64>,
+) -> Result<f64, CompileError> {
+    let mut parser = Parser { tokens, position: 0, open, lookup };
+    let value = parser.expression()?;
+    match tokens.get(parser.position) {
+        None => Ok(value),
+        Some(token) => Err(CompileError::at(token, format!("unexpected '{}' in expression", token.text))),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    open: &'a Token,
+    lookup: &'a dyn Fn(&str) -> Option<f64>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Result<&Token, CompileError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or_else(|| CompileError::at(self.open, "expression ends too early"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expression(&mut self) -> Result<f64, CompileError> {
+        let lhs = self.term()?;
+        let Some(op) = self.tokens.get(self.position).filter(|token| BINARY.contains(&token.text.as_str())) else {
+            return Ok(lhs);
+        };
+        let op = op.text.clone();
+        self.position += 1;
+        let rhs = self.expression()?;
+        let int = |value: f64| value as i64;
+        let truth = |condition: bool| if condition { 1.0 } else { 0.0 };
+        Ok(match op.as_str() {
+            "-" => lhs - rhs,
+            "+" => lhs + rhs,
+            "*" => lhs * rhs,
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "&" => (int(lhs) & int(rhs)) as f64,
+            "|" => (int(lhs) | int(rhs)) as f64,
+            "^" => (int(lhs) ^ int(rhs)) as f64,
+            "<<" => int(lhs).checked_shl(int(rhs) as u32).unwrap_or(0) as f64,
+            ">>" => int(lhs).checked_shr(int(rhs) as u32).unwrap_or(0) as f64,
+            "pow" => lhs.powf(rhs),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "<" => truth(lhs < rhs),
+            "<=" => truth(lhs <= rhs),
+            "==" => truth(lhs == rhs),
+            "!=" => truth(lhs != rhs),
+            ">=" => truth(lhs >= rhs),
+            _ => truth(lhs > rhs),
+        })
+    }
+
+    fn term(&mut self) -> Result<f64, CompileError> {
+        let token = self.next()?.clone();
+        if token.text == "(" {
+            let value = self.expression()?;
+            return match self.next() {
+                Ok(close) if close.text == ")" => Ok(value),
+                _ => Err(CompileError::at(&token, "unclosed '('")),
+            };
+        }
+        if UNARY.contains(&token.text.as_str()) {
+            let value = self.term()?;
+            return Ok(match token.text.as_str() {
+                "-" => -value,
+                "~" => !(value as i64) as f64,
+                "!" => (value == 0.0) as i64 as f64,
+                "sin" => value.sin(),
+                "cos" => value.cos(),
+                "tan" => value.tan(),
+                "exp" => value.exp(),
+                "log" => value.ln(),
+                "abs" => value.abs(),
+                "sqrt" => value.sqrt(),
+                "sign" => value.signum(),
+                "ceil" => value.ceil(),
+                _ => value.floor(),
+            });
+        }
+        match token.text.as_str() {
+            "PI" => return Ok(std::f64::consts::PI),
+            "E" => return Ok(std::f64::consts::E),
+            _ => {}
+        }
+        if let Some(value) = parse_number(&token.text) {
+            return Ok(value as f64);
+        }
+        (self.lookup)(&token.text).ok_or_else(|| CompileError::at(&token, format!("undefined name '{}'", token.text)))
+    }
+}
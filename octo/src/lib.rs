@@ -0,0 +1,809 @@
+//! Compiler for Octo, the high-level assembly language most modern CHIP-8,
+//! SUPER-CHIP and XO-CHIP programs are written in.
+//!
+//! Supported: `: label`, `:next`, `:const`, `:alias`, `:calc`, `:byte`,
+//! `:org`, `:unpack`, `:macro`, `loop`/`while`/`again`,
+//! `if ... then` and `if ... begin ... else ... end`, and every statement
+//! that maps onto an instruction. `:breakpoint`, `:monitor` and `:proto` are
+//! accepted and ignored.
+//!
+//! The program starts with a jump to `: main`, which must be defined.
+
+mod calc;
+mod tokens;
+
+use chip8_core::{Instruction, STARTING_ADDRESS};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use tokens::{parse_number, Token};
+
+/// Highest address a program can reach
+const MAX_ADDRESS: u32 = 0xFFFF;
+/// Guards against macros that expand into themselves forever
+const MAX_MACRO_EXPANSIONS: usize = 100_000;
+/// VF, used as scratch space by the comparison pseudo-ops
+const VF: u8 = 0xF;
+
+/// A compile error, located at a line and column of the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column
+    pub column: usize,
+    pub message: String,
+}
+
+impl CompileError {
+    pub(crate) fn at(token: &Token, message: impl Into<String>) -> Self {
+        Self {
+            line: token.line,
+            column: token.column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// A compiled program, ready for `Chip8::load_program`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    /// Bytes from `STARTING_ADDRESS` up to the last one written
+    pub bytes: Vec<u8>,
+    /// The address of every label
+    pub symbols: BTreeMap<String, u16>,
+}
+
+impl Program {
+    /// Formats the symbol table as one `0x0204 name` line per label, sorted by address
+    pub fn symbol_table(&self) -> String {
+        let mut symbols: Vec<_> = self.symbols.iter().collect();
+        symbols.sort_by_key(|&(name, &addr)| (addr, name));
+        symbols.iter().map(|(name, addr)| format!("{:#06X} {}\n", addr, name)).collect()
+    }
+}
+
+/// Compiles Octo source into a ROM
+pub fn compile(source: &str) -> Result<Program, CompileError> {
+    let tokens: VecDeque<Token> = tokens::tokenize(source).into();
+    let start = Token {
+        text: String::new(),
+        line: 1,
+        column: 1,
+    };
+    let mut compiler = Compiler {
+        tokens,
+        last: start.clone(),
+        rom: Vec::new(),
+        written: Vec::new(),
+        here: STARTING_ADDRESS as u32,
+        labels: BTreeMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        macros: HashMap::new(),
+        fixups: Vec::new(),
+        loops: Vec::new(),
+        branches: Vec::new(),
+        expansions: 0,
+    };
+
+    // Reserve the jump to main
+    compiler.emit(Instruction::Jump { addr: 0 }, &start)?;
+    while !compiler.tokens.is_empty() {
+        compiler.statement()?;
+    }
+    compiler.finish()
+}
+
+/// How a forward reference is patched once its label is known
+#[derive(Debug, Clone, Copy)]
+enum FixupKind {
+    /// The low 12 bits of an instruction
+    Address,
+    /// A whole 16-bit word
+    Long,
+    /// The byte of `v0 := ` in `:unpack`, with its nibble
+    UnpackHigh(u8),
+    /// The byte of `v1 := ` in `:unpack`
+    UnpackLow,
+}
+
+struct Fixup {
+    offset: usize,
+    kind: FixupKind,
+    name: Token,
+}
+
+struct Loop {
+    start: u16,
+    token: Token,
+    /// Offsets of the jumps out of the loop made by `while`
+    breaks: Vec<usize>,
+}
+
+struct Branch {
+    /// Offset of the jump to patch at `else` or `end`
+    jump: usize,
+    token: Token,
+    has_else: bool,
+}
+
+#[derive(Clone)]
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// The register operand and comparison of an `if` or `while`
+struct Condition {
+    x: u8,
+    op: Token,
+    rhs: Option<Token>,
+}
+
+struct Compiler {
+    tokens: VecDeque<Token>,
+    /// The most recently read token, for errors at the end of the source
+    last: Token,
+    rom: Vec<u8>,
+    written: Vec<bool>,
+    here: u32,
+    labels: BTreeMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    loops: Vec<Loop>,
+    branches: Vec<Branch>,
+    expansions: usize,
+}
+
+impl Compiler {
+    fn next(&mut self) -> Result<Token, CompileError> {
+        let token = self
+            .tokens
+            .pop_front()
+            .ok_or_else(|| CompileError::at(&self.last, "unexpected end of program"))?;
+        self.last = token.clone();
+        Ok(token)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, CompileError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(CompileError::at(&token, format!("expected '{}', found '{}'", text, token.text)));
+        }
+        Ok(token)
+    }
+
+    fn statement(&mut self) -> Result<(), CompileError> {
+        let token = self.next()?;
+        match token.text.as_str() {
+            ":" => {
+                let name = self.name()?;
+                self.define_label(&name, self.here)?;
+            }
+            ":next" => {
+                // Labels the byte after the next opcode, for self-modifying code
+                let name = self.name()?;
+                self.define_label(&name, self.here + 1)?;
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.next()?;
+                let value = self.known_value(&value)?;
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                self.aliases.insert(name.text, register);
+            }
+            ":calc" => {
+                let name = self.name()?;
+                let value = self.calc()?;
+                self.constants.insert(name.text, value);
+            }
+            ":byte" => {
+                let value = if self.tokens.front().is_some_and(|next| next.text == "{") {
+                    let open = self.tokens.front().cloned().unwrap();
+                    (self.calc()?, open)
+                } else {
+                    let value = self.next()?;
+                    (self.known_value(&value)?, value)
+                };
+                let byte = fit(&value.1, value.0, -0x80, 0xFF)? as u8;
+                self.emit_bytes(&[byte], &token)?;
+            }
+            ":org" => {
+                let value = self.next()?;
+                let addr = fit(&value, self.known_value(&value)?, 0, MAX_ADDRESS as i64)?;
+                self.here = addr as u32;
+            }
+            ":unpack" => self.unpack()?,
+            ":macro" => {
+                let name = self.name()?;
+                let mut args = Vec::new();
+                while self.tokens.front().is_some_and(|next| next.text != "{") {
+                    args.push(self.next()?.text);
+                }
+                let body = self.braced()?;
+                self.macros.insert(name.text, Macro { args, body });
+            }
+            ":breakpoint" | ":proto" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "return" | ";" => self.emit(Instruction::Return, &token)?,
+            "clear" => self.emit(Instruction::ClearScreen, &token)?,
+            "exit" => self.emit(Instruction::Exit, &token)?,
+            "lores" => self.emit(Instruction::LowRes, &token)?,
+            "hires" => self.emit(Instruction::HighRes, &token)?,
+            "scroll-left" => self.emit(Instruction::ScrollLeft, &token)?,
+            "scroll-right" => self.emit(Instruction::ScrollRight, &token)?,
+            "audio" => self.emit(Instruction::LoadAudioPattern, &token)?,
+            "scroll-down" | "scroll-up" | "plane" => {
+                let value = self.next()?;
+                let max = if token.text == "plane" { 3 } else { 0xF };
+                let n = fit(&value, self.known_value(&value)?, 0, max)? as u8;
+                let instruction = match token.text.as_str() {
+                    "scroll-down" => Instruction::ScrollDown { n },
+                    "scroll-up" => Instruction::ScrollUp { n },
+                    _ => Instruction::SelectPlanes { planes: n },
+                };
+                self.emit(instruction, &token)?;
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let x = self.register()?;
+                let instruction = match token.text.as_str() {
+                    "bcd" => Instruction::StoreBcd { x },
+                    "saveflags" => Instruction::StoreFlags { x },
+                    _ => Instruction::LoadFlags { x },
+                };
+                self.emit(instruction, &token)?;
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                let instruction = if self.tokens.front().is_some_and(|next| next.text == "-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    match token.text.as_str() {
+                        "save" => Instruction::SaveRange { x, y },
+                        _ => Instruction::LoadRange { x, y },
+                    }
+                } else {
+                    match token.text.as_str() {
+                        "save" => Instruction::StoreRegisters { x },
+                        _ => Instruction::LoadRegisters { x },
+                    }
+                };
+                self.emit(instruction, &token)?;
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let value = self.next()?;
+                let n = fit(&value, self.known_value(&value)?, 0, 0xF)? as u8;
+                self.emit(Instruction::Draw { x, y, n }, &token)?;
+            }
+            "jump" | "jump0" => {
+                let target = self.next()?;
+                let addr = self.address(&target, self.offset(&token)?)?;
+                let instruction = match token.text.as_str() {
+                    "jump" => Instruction::Jump { addr },
+                    _ => Instruction::JumpOffset { addr },
+                };
+                self.emit(instruction, &token)?;
+            }
+            "native" => {
+                let target = self.next()?;
+                let addr = self.address(&target, self.offset(&token)?)?;
+                self.emit_bytes(&addr.to_be_bytes(), &token)?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let instruction = match token.text.as_str() {
+                    "delay" => Instruction::SetDelayTimer { x },
+                    "buzzer" => Instruction::SetSoundTimer { x },
+                    _ => Instruction::SetPitch { x },
+                };
+                self.emit(instruction, &token)?;
+            }
+            "i" => self.index(&token)?,
+            "loop" => self.loops.push(Loop {
+                start: self.address_here(&token)?,
+                token,
+                breaks: Vec::new(),
+            }),
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err(CompileError::at(&token, "'while' outside of a loop"));
+                }
+                let condition = self.condition()?;
+                self.emit_condition(&condition, true)?;
+                let jump = self.offset(&token)?;
+                self.emit(Instruction::Jump { addr: 0 }, &token)?;
+                self.loops.last_mut().unwrap().breaks.push(jump);
+            }
+            "again" => {
+                let Some(lp) = self.loops.pop() else {
+                    return Err(CompileError::at(&token, "'again' without 'loop'"));
+                };
+                self.emit(Instruction::Jump { addr: lp.start }, &token)?;
+                let end = self.address_here(&token)?;
+                for jump in lp.breaks {
+                    self.patch_address(jump, end, &token)?;
+                }
+            }
+            "if" => {
+                let condition = self.condition()?;
+                let form = self.next()?;
+                match form.text.as_str() {
+                    "then" => self.emit_condition(&condition, false)?,
+                    "begin" => {
+                        self.emit_condition(&condition, true)?;
+                        let jump = self.offset(&form)?;
+                        self.emit(Instruction::Jump { addr: 0 }, &form)?;
+                        self.branches.push(Branch {
+                            jump,
+                            token: form,
+                            has_else: false,
+                        });
+                    }
+                    _ => return Err(CompileError::at(&form, "expected 'then' or 'begin'")),
+                }
+            }
+            "else" => {
+                let jump = self.offset(&token)?;
+                let Some(branch) = self.branches.last_mut().filter(|branch| !branch.has_else) else {
+                    return Err(CompileError::at(&token, "'else' without 'begin'"));
+                };
+                let previous = std::mem::replace(&mut branch.jump, jump);
+                branch.has_else = true;
+                self.emit(Instruction::Jump { addr: 0 }, &token)?;
+                let here = self.address_here(&token)?;
+                self.patch_address(previous, here, &token)?;
+            }
+            "end" => {
+                let Some(branch) = self.branches.pop() else {
+                    return Err(CompileError::at(&token, "'end' without 'begin'"));
+                };
+                let here = self.address_here(&token)?;
+                self.patch_address(branch.jump, here, &token)?;
+            }
+            _ => {
+                if let Some(x) = self.register_of(&token.text) {
+                    return self.assignment(x);
+                }
+                if let Some(definition) = self.macros.get(&token.text).cloned() {
+                    return self.expand(&token, definition);
+                }
+                if token.text.starts_with(':') || is_reserved(&token.text) {
+                    return Err(CompileError::at(&token, format!("unexpected '{}'", token.text)));
+                }
+                if parse_number(&token.text).is_some() || self.constants.contains_key(&token.text) {
+                    let value = self.known_value(&token)?;
+                    let byte = fit(&token, value, -0x80, 0xFF)? as u8;
+                    return self.emit_bytes(&[byte], &token);
+                }
+                // Any other name is a call, possibly to a label defined later
+                let addr = self.address(&token, self.offset(&token)?)?;
+                self.emit(Instruction::Call { addr }, &token)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a name for a label, constant, alias or macro
+    fn name(&mut self) -> Result<Token, CompileError> {
+        let token = self.next()?;
+        if parse_number(&token.text).is_some() || self.register_of(&token.text).is_some() || is_reserved(&token.text) {
+            return Err(CompileError::at(&token, format!("'{}' cannot be used as a name", token.text)));
+        }
+        Ok(token)
+    }
+
+    fn define_label(&mut self, name: &Token, addr: u32) -> Result<(), CompileError> {
+        if addr > MAX_ADDRESS {
+            return Err(CompileError::at(name, "label is past the end of memory"));
+        }
+        if self.labels.insert(name.text.clone(), addr as u16).is_some() {
+            return Err(CompileError::at(name, format!("'{}' is already defined", name.text)));
+        }
+        Ok(())
+    }
+
+    fn register_of(&self, text: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(text) {
+            return Some(register);
+        }
+        let digit = text.strip_prefix(['v', 'V']).filter(|digit| digit.len() == 1)?;
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn register(&mut self) -> Result<u8, CompileError> {
+        let token = self.next()?;
+        self.register_of(&token.text)
+            .ok_or_else(|| CompileError::at(&token, format!("expected a register, found '{}'", token.text)))
+    }
+
+    /// Resolves a number, constant or already defined label
+    fn known_value(&self, token: &Token) -> Result<f64, CompileError> {
+        if let Some(value) = parse_number(&token.text) {
+            return Ok(value as f64);
+        }
+        if let Some(&value) = self.constants.get(&token.text) {
+            return Ok(value);
+        }
+        if let Some(&addr) = self.labels.get(&token.text) {
+            return Ok(addr as f64);
+        }
+        Err(CompileError::at(token, format!("undefined name '{}'", token.text)))
+    }
+
+    /// Resolves a 12-bit address, recording a fixup for the instruction at
+    /// `offset` if it names a label that is not defined yet
+    fn address(&mut self, token: &Token, offset: usize) -> Result<u16, CompileError> {
+        match self.known_value(token) {
+            Ok(value) => Ok(fit(token, value, 0, 0xFFF)? as u16),
+            Err(_) if parse_number(&token.text).is_none() && self.register_of(&token.text).is_none() => {
+                self.fixups.push(Fixup {
+                    offset,
+                    kind: FixupKind::Address,
+                    name: token.clone(),
+                });
+                Ok(0)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Reads a `{ expression }` and evaluates it
+    fn calc(&mut self) -> Result<f64, CompileError> {
+        let open = self.tokens.front().cloned().unwrap_or_else(|| self.last.clone());
+        let body = self.braced()?;
+        let here = self.here as f64;
+        calc::evaluate(&body, &open, &|name| {
+            if name == "HERE" {
+                return Some(here);
+            }
+            self.constants
+                .get(name)
+                .copied()
+                .or_else(|| self.labels.get(name).map(|&addr| addr as f64))
+        })
+    }
+
+    /// Reads the tokens between a `{` and its matching `}`
+    fn braced(&mut self) -> Result<Vec<Token>, CompileError> {
+        self.expect("{")?;
+        let mut depth = 0;
+        let mut body = Vec::new();
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => return Ok(body),
+                "}" => depth -= 1,
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    fn expand(&mut self, token: &Token, definition: Macro) -> Result<(), CompileError> {
+        self.expansions += 1;
+        if self.expansions > MAX_MACRO_EXPANSIONS {
+            return Err(CompileError::at(token, format!("macro '{}' expands forever", token.text)));
+        }
+        let mut args = HashMap::new();
+        for name in &definition.args {
+            args.insert(name.clone(), self.next()?.text);
+        }
+        for body_token in definition.body.into_iter().rev() {
+            let text = args.get(&body_token.text).cloned().unwrap_or(body_token.text);
+            self.tokens.push_front(Token { text, ..body_token });
+        }
+        Ok(())
+    }
+
+    /// `:unpack N label` loads v0 with N and the high nibble of the address, and v1 with the low byte
+    fn unpack(&mut self) -> Result<(), CompileError> {
+        let value = self.next()?;
+        let nibble = fit(&value, self.known_value(&value)?, 0, 0xF)? as u8;
+        let target = self.next()?;
+        let offset = self.offset(&target)?;
+        let addr = match self.known_value(&target) {
+            Ok(value) => fit(&target, value, 0, 0xFFF)? as u16,
+            Err(_) => {
+                self.fixups.push(Fixup {
+                    offset: offset + 1,
+                    kind: FixupKind::UnpackHigh(nibble),
+                    name: target.clone(),
+                });
+                self.fixups.push(Fixup {
+                    offset: offset + 3,
+                    kind: FixupKind::UnpackLow,
+                    name: target.clone(),
+                });
+                0
+            }
+        };
+        let high = (nibble << 4) | (addr >> 8) as u8;
+        self.emit(Instruction::LoadByte { x: 0, byte: high }, &target)?;
+        self.emit(Instruction::LoadByte { x: 1, byte: addr as u8 }, &target)
+    }
+
+    fn index(&mut self, token: &Token) -> Result<(), CompileError> {
+        let op = self.next()?;
+        match op.text.as_str() {
+            ":=" => {}
+            "+=" => {
+                let x = self.register()?;
+                return self.emit(Instruction::AddIndex { x }, token);
+            }
+            _ => return Err(CompileError::at(&op, "expected ':=' or '+=' after 'i'")),
+        }
+
+        let rhs = self.next()?;
+        match rhs.text.as_str() {
+            "hex" => {
+                let x = self.register()?;
+                self.emit(Instruction::LoadFont { x }, token)
+            }
+            "bighex" => {
+                let x = self.register()?;
+                self.emit(Instruction::LoadBigFont { x }, token)
+            }
+            "long" => {
+                let target = self.next()?;
+                let offset = self.offset(token)?;
+                let addr = match self.known_value(&target) {
+                    Ok(value) => fit(&target, value, 0, MAX_ADDRESS as i64)? as u16,
+                    Err(_) => {
+                        self.fixups.push(Fixup {
+                            offset: offset + 2,
+                            kind: FixupKind::Long,
+                            name: target.clone(),
+                        });
+                        0
+                    }
+                };
+                self.emit(Instruction::LoadLongIndex, token)?;
+                self.emit_bytes(&addr.to_be_bytes(), &target)
+            }
+            _ => {
+                let addr = self.address(&rhs, self.offset(token)?)?;
+                self.emit(Instruction::LoadIndex { addr }, token)
+            }
+        }
+    }
+
+    fn assignment(&mut self, x: u8) -> Result<(), CompileError> {
+        let op = self.next()?;
+        let rhs = self.next()?;
+        let y = self.register_of(&rhs.text);
+        let instruction = match (op.text.as_str(), y) {
+            (":=", Some(y)) => Instruction::Move { x, y },
+            (":=", None) => match rhs.text.as_str() {
+                "delay" => Instruction::LoadDelayTimer { x },
+                "key" => Instruction::WaitForKey { x },
+                "random" => {
+                    let mask = self.next()?;
+                    Instruction::Random { x, byte: self.byte(&mask)? }
+                }
+                _ => Instruction::LoadByte { x, byte: self.byte(&rhs)? },
+            },
+            ("+=", Some(y)) => Instruction::Add { x, y },
+            ("+=", None) => Instruction::AddByte { x, byte: self.byte(&rhs)? },
+            ("-=", Some(y)) => Instruction::Sub { x, y },
+            ("-=", None) => Instruction::AddByte {
+                x,
+                byte: self.byte(&rhs)?.wrapping_neg(),
+            },
+            ("=-", Some(y)) => Instruction::SubReverse { x, y },
+            ("|=", Some(y)) => Instruction::Or { x, y },
+            ("&=", Some(y)) => Instruction::And { x, y },
+            ("^=", Some(y)) => Instruction::Xor { x, y },
+            (">>=", Some(y)) => Instruction::ShiftRight { x, y },
+            ("<<=", Some(y)) => Instruction::ShiftLeft { x, y },
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", None) => {
+                return Err(CompileError::at(&rhs, format!("'{}' needs a register", op.text)));
+            }
+            _ => return Err(CompileError::at(&op, format!("unknown operator '{}'", op.text))),
+        };
+        self.emit(instruction, &op)
+    }
+
+    fn byte(&self, token: &Token) -> Result<u8, CompileError> {
+        Ok(fit(token, self.known_value(token)?, -0x80, 0xFF)? as u8)
+    }
+
+    fn condition(&mut self) -> Result<Condition, CompileError> {
+        let x = self.register()?;
+        let op = self.next()?;
+        let rhs = match op.text.as_str() {
+            "key" | "-key" => None,
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => Some(self.next()?),
+            _ => return Err(CompileError::at(&op, format!("unknown comparison '{}'", op.text))),
+        };
+        Ok(Condition { x, op, rhs })
+    }
+
+    /// Emits the skip for a condition; it skips the next instruction when the
+    /// condition's truth equals `skip_when`
+    fn emit_condition(&mut self, condition: &Condition, skip_when: bool) -> Result<(), CompileError> {
+        let Condition { x, op, rhs } = condition;
+        let x = *x;
+        let y = rhs.as_ref().and_then(|rhs| self.register_of(&rhs.text));
+        let byte = match (rhs, y) {
+            (Some(rhs), None) => Some(self.byte(rhs)?),
+            _ => None,
+        };
+
+        let instruction = match op.text.as_str() {
+            "key" | "-key" => {
+                if (op.text == "key") == skip_when {
+                    Instruction::SkipIfKeyPressed { x }
+                } else {
+                    Instruction::SkipIfKeyNotPressed { x }
+                }
+            }
+            "==" | "!=" => {
+                let skip_if_equal = (op.text == "==") == skip_when;
+                match (y, byte, skip_if_equal) {
+                    (Some(y), _, true) => Instruction::SkipIfRegistersEqual { x, y },
+                    (Some(y), _, false) => Instruction::SkipIfRegistersNotEqual { x, y },
+                    (None, Some(byte), true) => Instruction::SkipIfEqual { x, byte },
+                    (None, Some(byte), false) => Instruction::SkipIfNotEqual { x, byte },
+                    (None, None, _) => unreachable!("comparisons always have a right-hand side"),
+                }
+            }
+            _ => {
+                // VF = the right-hand side, then subtract so VF holds the borrow flag
+                let load = match (y, byte) {
+                    (Some(y), _) => Instruction::Move { x: VF, y },
+                    (None, byte) => Instruction::LoadByte {
+                        x: VF,
+                        byte: byte.unwrap_or_default(),
+                    },
+                };
+                self.emit(load, op)?;
+                let (subtract, holds_when_true) = match op.text.as_str() {
+                    // VF = rhs - x, so the flag is rhs >= x
+                    ">" => (Instruction::Sub { x: VF, y: x }, 0),
+                    "<=" => (Instruction::Sub { x: VF, y: x }, 1),
+                    // VF = x - rhs, so the flag is x >= rhs
+                    "<" => (Instruction::SubReverse { x: VF, y: x }, 0),
+                    _ => (Instruction::SubReverse { x: VF, y: x }, 1),
+                };
+                self.emit(subtract, op)?;
+                if skip_when {
+                    Instruction::SkipIfEqual { x: VF, byte: holds_when_true }
+                } else {
+                    Instruction::SkipIfNotEqual { x: VF, byte: holds_when_true }
+                }
+            }
+        };
+        self.emit(instruction, op)
+    }
+
+    /// The current address, which must fit in 12 bits for jumps to reach it
+    fn address_here(&self, token: &Token) -> Result<u16, CompileError> {
+        fit(token, self.here as f64, 0, 0xFFF).map(|addr| addr as u16)
+    }
+
+    /// The offset into the ROM of the current address
+    fn offset(&self, token: &Token) -> Result<usize, CompileError> {
+        match self.here.checked_sub(STARTING_ADDRESS as u32) {
+            Some(offset) => Ok(offset as usize),
+            None => Err(CompileError::at(
+                token,
+                format!("address {:#X} is below the program start {:#X}", self.here, STARTING_ADDRESS),
+            )),
+        }
+    }
+
+    fn emit(&mut self, instruction: Instruction, token: &Token) -> Result<(), CompileError> {
+        self.emit_bytes(&instruction.encode().to_be_bytes(), token)
+    }
+
+    fn emit_bytes(&mut self, bytes: &[u8], token: &Token) -> Result<(), CompileError> {
+        for &byte in bytes {
+            if self.here > MAX_ADDRESS {
+                return Err(CompileError::at(token, "program runs past the end of memory"));
+            }
+            let offset = self.offset(token)?;
+            if self.rom.len() <= offset {
+                self.rom.resize(offset + 1, 0);
+                self.written.resize(offset + 1, false);
+            }
+            if self.written[offset] {
+                return Err(CompileError::at(token, format!("address {:#X} is written twice", self.here)));
+            }
+            self.rom[offset] = byte;
+            self.written[offset] = true;
+            self.here += 1;
+        }
+        Ok(())
+    }
+
+    fn patch_address(&mut self, offset: usize, addr: u16, token: &Token) -> Result<(), CompileError> {
+        if addr > 0xFFF {
+            return Err(CompileError::at(token, format!("address {:#X} does not fit in 12 bits", addr)));
+        }
+        self.rom[offset] = (self.rom[offset] & 0xF0) | (addr >> 8) as u8;
+        self.rom[offset + 1] = addr as u8;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Program, CompileError> {
+        if let Some(lp) = self.loops.last() {
+            return Err(CompileError::at(&lp.token, "'loop' without 'again'"));
+        }
+        if let Some(branch) = self.branches.last() {
+            return Err(CompileError::at(&branch.token, "'begin' without 'end'"));
+        }
+
+        let Some(&main) = self.labels.get("main") else {
+            return Err(CompileError::at(&self.last, "the program has no ': main' label"));
+        };
+        let start = self.last.clone();
+        self.patch_address(0, main, &start)?;
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&addr) = self.labels.get(&fixup.name.text) else {
+                return Err(CompileError::at(&fixup.name, format!("undefined name '{}'", fixup.name.text)));
+            };
+            match fixup.kind {
+                FixupKind::Address => self.patch_address(fixup.offset, addr, &fixup.name)?,
+                FixupKind::Long => self.rom[fixup.offset..fixup.offset + 2].copy_from_slice(&addr.to_be_bytes()),
+                FixupKind::UnpackHigh(nibble) => {
+                    if addr > 0xFFF {
+                        return Err(CompileError::at(&fixup.name, "':unpack' needs a 12-bit address"));
+                    }
+                    self.rom[fixup.offset] = (nibble << 4) | (addr >> 8) as u8;
+                }
+                FixupKind::UnpackLow => self.rom[fixup.offset] = addr as u8,
+            }
+        }
+
+        let end = self.written.iter().rposition(|&written| written).map_or(0, |last| last + 1);
+        self.rom.truncate(end);
+        Ok(Program {
+            bytes: self.rom,
+            symbols: self.labels,
+        })
+    }
+}
+
+/// Words with a meaning of their own, which cannot be used as names or calls
+fn is_reserved(text: &str) -> bool {
+    matches!(
+        text,
+        ":=" | "+=" | "-=" | "=-" | "|=" | "&=" | "^=" | ">>=" | "<<=" | "==" | "!=" | "<" | ">" | "<=" | ">="
+            | "{" | "}" | "then" | "begin" | "key" | "-key" | "hex" | "bighex" | "long" | "random" | "delay"
+            | "buzzer" | "pitch" | "i"
+    )
+}
+
+/// Truncates a value and checks that it fits a field
+fn fit(token: &Token, value: f64, min: i64, max: i64) -> Result<i64, CompileError> {
+    let value = value.floor() as i64;
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(CompileError::at(token, format!("value {} does not fit in {}..={}", value, min, max)))
+    }
+}
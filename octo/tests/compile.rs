@@ -0,0 +1,153 @@
+use chip8_core::{Chip8, Instruction};
+use octo::compile;
+
+/// Disassembles compiled bytes, one mnemonic per instruction
+fn listing(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(2)
+        .map(|pair| Instruction::decode(u16::from_be_bytes([pair[0], pair[1]])).unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn control_flow_compiles_to_skips_and_jumps() {
+    let program = compile(
+        "
+        : main
+            v0 := 0
+            loop
+                v0 += 1
+                while v0 != 10
+                if v0 == 5 then v1 := v0
+            again
+            if v1 key begin
+                clear
+            else
+                v2 := random 0x0F
+            end
+            draw-thing
+            ;
+        : draw-thing
+            i := dot
+            sprite v0 v1 1
+            return
+        : dot
+            0b10000000
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(
+        listing(&program.bytes[..program.bytes.len() - 1]),
+        [
+            "JP 0x202",
+            "LD V0, 0x00",
+            "ADD V0, 0x01",
+            "SNE V0, 0x0A",
+            "JP 0x210",
+            "SNE V0, 0x05",
+            "LD V1, V0",
+            "JP 0x204",
+            "SKP V1",
+            "JP 0x218",
+            "CLS",
+            "JP 0x21A",
+            "RND V2, 0x0F",
+            "CALL 0x21E",
+            "RET",
+            "LD I, 0x224",
+            "DRW V0, V1, 1",
+            "RET",
+        ]
+    );
+    assert_eq!(program.bytes.last(), Some(&0x80));
+    assert_eq!(program.symbols["draw-thing"], 0x21E);
+    assert_eq!(program.symbols["dot"], 0x224);
+}
+
+#[test]
+fn constants_aliases_macros_and_calc() {
+    let program = compile(
+        "
+        :const SPEED 3
+        :alias px v4
+        :calc DOUBLE { SPEED * 2 }
+        :calc MIXED { 2 * 3 + 1 }
+        :macro step reg amount { reg += amount }
+        : main
+            step px SPEED
+            px -= DOUBLE
+            :unpack 0xA table
+            i := long table
+            :byte { MIXED - 1 }
+        : table
+            save v3 - v5
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(
+        listing(&program.bytes[..6]),
+        ["JP 0x202", "ADD V4, 0x03", "ADD V4, 0xFA"]
+    );
+    // Right-to-left evaluation: 2 * (3 + 1) = 8, so the byte is 7
+    assert_eq!(
+        &program.bytes[6..],
+        [0x60, 0xA2, 0x61, 0x0F, 0xF0, 0x00, 0x02, 0x0F, 0x07, 0x53, 0x52]
+    );
+    assert_eq!(program.symbol_table(), "0x0202 main\n0x020F table\n");
+}
+
+/// Runs a comparison on the core and reports whether the `then` branch ran
+fn comparison_holds(a: u8, op: &str, b: &str) -> bool {
+    let source = format!(
+        ": main
+            v0 := {}
+            v1 := 7
+            v2 := 0
+            if v0 {} {} then v2 := 1
+            i := flag
+            v3 := 0
+            if v2 == 1 then sprite v3 v3 1
+            loop again
+        : flag 0x80",
+        a, op, b
+    );
+    let program = compile(&source).unwrap();
+    let mut chip8 = Chip8::initialize();
+    chip8.load_program(&program.bytes).unwrap();
+    for _ in 0..20 {
+        chip8.cycle().unwrap();
+        chip8.update_timers();
+    }
+    chip8.get_framebuffer().pixels[0] != 0
+}
+
+#[test]
+fn comparison_pseudo_ops_match_their_meaning() {
+    for a in [0u8, 6, 7, 8, 255] {
+        for rhs in ["v1", "7"] {
+            assert_eq!(comparison_holds(a, "<", rhs), a < 7, "{} < {}", a, rhs);
+            assert_eq!(comparison_holds(a, ">", rhs), a > 7, "{} > {}", a, rhs);
+            assert_eq!(comparison_holds(a, "<=", rhs), a <= 7, "{} <= {}", a, rhs);
+            assert_eq!(comparison_holds(a, ">=", rhs), a >= 7, "{} >= {}", a, rhs);
+            assert_eq!(comparison_holds(a, "==", rhs), a == 7, "{} == {}", a, rhs);
+            assert_eq!(comparison_holds(a, "!=", rhs), a != 7, "{} != {}", a, rhs);
+        }
+    }
+}
+
+#[test]
+fn errors_report_line_and_column() {
+    let err = compile(": main\n  v0 := 300").unwrap_err();
+    assert_eq!(err.to_string(), "2:9: value 300 does not fit in -128..=255");
+
+    let err = compile(": main\n  missing").unwrap_err();
+    assert_eq!((err.line, err.column), (2, 3));
+
+    let err = compile(": main\n  loop\n  v0 += 1").unwrap_err();
+    assert_eq!(err.to_string(), "2:3: 'loop' without 'again'");
+
+    let err = compile("v0 := 1").unwrap_err();
+    assert!(err.message.contains("main"), "{}", err);
+}